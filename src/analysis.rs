@@ -0,0 +1,257 @@
+use std::collections::{BTreeSet, HashSet};
+use tera::{
+    ast::{Expr, ExprVal, FunctionCall, LogicOperator, Node},
+    Template,
+};
+
+/// Names tera binds on its own, which never come from the context.
+const RESERVED_NAMES: [&str; 2] = ["loop", "__tera_context"];
+
+/// Parses `input` as a tera template and returns the root name of every context variable it
+/// references, e.g. `{{ author.name | upper }}` yields `author`.
+///
+/// Variables bound inside the template (`set`, `for` loops and macro arguments) are not reported,
+/// and neither are variables guarded with the `default` filter, nor those tested with `defined` or
+/// `undefined`, within the branches of the `if` where the test proves them defined.
+pub fn referenced_variables(input: &str) -> Result<BTreeSet<String>, tera::Error> {
+    let template = Template::new("__kopye_analysis", None, input)?;
+
+    let mut found = BTreeSet::new();
+    let mut bound = RESERVED_NAMES.iter().map(|name| name.to_string()).collect();
+
+    walk_nodes(&template.ast, &mut bound, &mut found);
+
+    Ok(found)
+}
+
+/// Splits an identifier such as `a.b[c].d` into the variables it reads: `a` and `c`.
+fn identifier_roots(ident: &str) -> Vec<&str> {
+    lazy_static::lazy_static! {
+        static ref INDEX_REGEX: regex::Regex =
+            regex::Regex::new(r"\[([A-Za-z_][A-Za-z0-9_]*)").expect("a valid regex pattern");
+    }
+
+    let root = ident.split(['.', '[']).next().unwrap_or(ident);
+
+    std::iter::once(root)
+        .chain(
+            INDEX_REGEX
+                .captures_iter(ident)
+                .filter_map(|captures| captures.get(1).map(|m| m.as_str())),
+        )
+        .collect()
+}
+
+fn insert_ident(ident: &str, bound: &HashSet<String>, found: &mut BTreeSet<String>) {
+    for root in identifier_roots(ident) {
+        if !bound.contains(root) {
+            found.insert(root.to_string());
+        }
+    }
+}
+
+fn walk_nodes(nodes: &[Node], bound: &mut HashSet<String>, found: &mut BTreeSet<String>) {
+    for node in nodes {
+        match node {
+            Node::VariableBlock(_, expr) => walk_expr(expr, bound, found),
+            Node::MacroDefinition(_, definition, _) => {
+                let mut scope = bound.clone();
+                scope.extend(definition.args.keys().cloned());
+
+                for default in definition.args.values().flatten() {
+                    walk_expr(default, bound, found);
+                }
+                walk_nodes(&definition.body, &mut scope, found);
+            }
+            Node::Set(_, set) => {
+                walk_expr(&set.value, bound, found);
+                bound.insert(set.key.clone());
+            }
+            Node::FilterSection(_, section, _) => {
+                walk_function_call(&section.filter, bound, found);
+                walk_nodes(&section.body, bound, found);
+            }
+            Node::Block(_, block, _) => walk_nodes(&block.body, bound, found),
+            Node::Forloop(_, forloop, _) => {
+                walk_expr(&forloop.container, bound, found);
+
+                let mut scope = bound.clone();
+                scope.insert(forloop.value.clone());
+                if let Some(key) = &forloop.key {
+                    scope.insert(key.clone());
+                }
+
+                walk_nodes(&forloop.body, &mut scope, found);
+                if let Some(empty_body) = &forloop.empty_body {
+                    walk_nodes(empty_body, bound, found);
+                }
+            }
+            Node::If(conditional, _) => {
+                // names known to be defined once every previous condition failed
+                let mut otherwise = bound.clone();
+
+                for (_, condition, body) in &conditional.conditions {
+                    walk_expr(condition, &otherwise, found);
+
+                    let (when_true, when_false) = defined_names(condition);
+
+                    let mut scope = otherwise.clone();
+                    scope.extend(when_true);
+                    walk_nodes(body, &mut scope, found);
+
+                    otherwise.extend(when_false);
+                }
+                if let Some((_, body)) = &conditional.otherwise {
+                    walk_nodes(body, &mut otherwise, found);
+                }
+            }
+            Node::Super
+            | Node::Text(_)
+            | Node::Extends(_, _)
+            | Node::Include(_, _, _)
+            | Node::ImportMacro(_, _, _)
+            | Node::Raw(_, _, _)
+            | Node::Break(_)
+            | Node::Continue(_)
+            | Node::Comment(_, _) => {}
+        }
+    }
+}
+
+/// The names a condition proves to be defined when it holds, and when it does not, e.g.
+/// `license is defined` and `license is undefined` respectively for `license`.
+fn defined_names(expr: &Expr) -> (Vec<String>, Vec<String>) {
+    match &expr.val {
+        ExprVal::Test(test) if matches!(test.name.as_str(), "defined" | "undefined") => {
+            let root = identifier_roots(&test.ident)[0].to_string();
+            let is_defined = (test.name == "defined") ^ test.negated ^ expr.negated;
+
+            match is_defined {
+                true => (vec![root], Vec::new()),
+                false => (Vec::new(), vec![root]),
+            }
+        }
+        ExprVal::Logic(logic) if !expr.negated => {
+            let (lhs_true, lhs_false) = defined_names(&logic.lhs);
+            let (rhs_true, rhs_false) = defined_names(&logic.rhs);
+
+            match logic.operator {
+                LogicOperator::And => ([lhs_true, rhs_true].concat(), Vec::new()),
+                LogicOperator::Or => (Vec::new(), [lhs_false, rhs_false].concat()),
+                _ => (Vec::new(), Vec::new()),
+            }
+        }
+        _ => (Vec::new(), Vec::new()),
+    }
+}
+
+fn walk_expr(expr: &Expr, bound: &HashSet<String>, found: &mut BTreeSet<String>) {
+    // `{{ license | default(value="MIT") }}` is allowed to be missing from the context
+    if !(expr.has_default_filter() && matches!(expr.val, ExprVal::Ident(_))) {
+        walk_expr_val(&expr.val, bound, found);
+    }
+
+    for filter in &expr.filters {
+        walk_function_call(filter, bound, found);
+    }
+}
+
+fn walk_function_call(call: &FunctionCall, bound: &HashSet<String>, found: &mut BTreeSet<String>) {
+    for arg in call.args.values() {
+        walk_expr(arg, bound, found);
+    }
+}
+
+fn walk_expr_val(val: &ExprVal, bound: &HashSet<String>, found: &mut BTreeSet<String>) {
+    match val {
+        ExprVal::Ident(ident) => insert_ident(ident, bound, found),
+        ExprVal::Math(math) => {
+            walk_expr(&math.lhs, bound, found);
+            walk_expr(&math.rhs, bound, found);
+        }
+        ExprVal::Logic(logic) => {
+            walk_expr(&logic.lhs, bound, found);
+            walk_expr(&logic.rhs, bound, found);
+        }
+        ExprVal::Test(test) => {
+            if !matches!(test.name.as_str(), "defined" | "undefined") {
+                insert_ident(&test.ident, bound, found);
+            }
+            for arg in &test.args {
+                walk_expr(arg, bound, found);
+            }
+        }
+        ExprVal::MacroCall(call) => {
+            for arg in call.args.values() {
+                walk_expr(arg, bound, found);
+            }
+        }
+        ExprVal::FunctionCall(call) => walk_function_call(call, bound, found),
+        ExprVal::Array(values) => {
+            for value in values {
+                walk_expr(value, bound, found);
+            }
+        }
+        ExprVal::StringConcat(concat) => {
+            for value in &concat.values {
+                walk_expr_val(value, bound, found);
+            }
+        }
+        ExprVal::In(membership) => {
+            walk_expr(&membership.lhs, bound, found);
+            walk_expr(&membership.rhs, bound, found);
+        }
+        ExprVal::String(_) | ExprVal::Int(_) | ExprVal::Float(_) | ExprVal::Bool(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(input: &str) -> Vec<String> {
+        referenced_variables(input).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn test_referenced_variables_finds_roots() {
+        assert_eq!(
+            names("{{ author.name | upper }} {% if ci == 'github' %}{{ items[idx] }}{% endif %}"),
+            vec!["author", "ci", "idx", "items"]
+        );
+    }
+
+    #[test]
+    fn test_referenced_variables_skips_bound_and_guarded_names() {
+        assert_eq!(
+            names(
+                "{% set x = project %}{{ x }}\
+                 {% for t in targets %}{{ t }}{{ loop.index }}{% endfor %}\
+                 {{ license | default(value='MIT') }}{% if docs is defined %}{% endif %}"
+            ),
+            vec!["project", "targets"]
+        );
+    }
+
+    #[test]
+    fn test_referenced_variables_binds_defined_names_in_guarded_branches() {
+        assert!(names("{% if license is defined %}{{ license }}{% endif %}").is_empty());
+        assert!(names(
+            "{% if license is undefined %}MIT{% elif ci %}{{ license }}{% else %}{{ license.name }}{% endif %}"
+        )
+        .eq(&["ci"]));
+        assert!(
+            names("{% if a is defined and b is defined %}{{ a }}{{ b }}{% endif %}").is_empty()
+        );
+
+        // only the guarded branch may use it
+        assert_eq!(
+            names("{% if license is defined %}{% else %}{{ license }}{% endif %}{{ license }}"),
+            vec!["license"]
+        );
+        assert_eq!(
+            names("{% if license is not defined %}{{ license }}{% endif %}"),
+            vec!["license"]
+        );
+    }
+}
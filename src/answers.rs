@@ -0,0 +1,276 @@
+use crate::{
+    errors::{FileFormat, FileOperation, IoError, ParseError},
+    prompt::{Answer, Question, QuestionType},
+    utils::normalize_path,
+};
+use indexmap::IndexMap;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum AnswersError {
+    #[error("I/O error within answers domain")]
+    #[diagnostic(code(kopye::answers::io))]
+    Io(#[from] IoError),
+
+    #[error("Parsing error within answers domain")]
+    #[diagnostic(
+        code(kopye::answers::parse),
+        help("Fix or remove .kopye-answers.toml, add-ons then ask every question again")
+    )]
+    Parse(#[from] ParseError),
+
+    #[error("Unable to serialize the answers of `{blueprint}`")]
+    #[diagnostic(code(kopye::answers::serialize))]
+    Serialize {
+        blueprint: String,
+        #[source]
+        source: toml::ser::Error,
+    },
+}
+
+/// Name of the file, at the root of a generated project, remembering its answers.
+pub const ANSWERS_FILE: &str = ".kopye-answers.toml";
+
+const HEADER: &str = "# Answers this project was generated with, read by kopye add-ons.
+# Secrets and computed values are never recorded: add-ons ask for secrets again, and compute
+# their values from these answers.
+";
+
+/// Where a project comes from, under the `[kopye]` table of [`ANSWERS_FILE`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The source url, or the directory of a local source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Every blueprint applied to the project, in order
+    #[serde(default)]
+    pub blueprints: Vec<String>,
+    /// Answers holding a path, stored relative to the project when they lie within it, so that
+    /// they hold on any checkout of it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+/// The answers a project was generated with, keyed by question name, as stored in
+/// [`ANSWERS_FILE`]. Secrets and computed values are never stored.
+///
+/// Path answers are absolute in memory, and only relative to the project in the file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnswersFile {
+    // answers come first, as toml emits plain values before tables
+    #[serde(flatten)]
+    pub answers: IndexMap<String, toml::Value>,
+    #[serde(default)]
+    pub kopye: Provenance,
+}
+impl AnswersFile {
+    /// Loads the answers file of the project at `destination`, which is empty when there is none.
+    pub fn load(destination: &Path) -> Result<Self, AnswersError> {
+        let path = destination.join(ANSWERS_FILE);
+
+        if !path.is_file() {
+            return Ok(AnswersFile::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|error| IoError::new(FileOperation::Read, path.clone(), error))?;
+
+        let mut file: AnswersFile = toml::from_str(&content)
+            .map_err(|error| ParseError::new(FileFormat::Toml, path, error))?;
+
+        let root = project_root(destination);
+        file.map_paths(|path| normalize_path(&root.join(path).display().to_string()));
+
+        Ok(file)
+    }
+
+    /// Records the `answers` of a blueprint, replacing previous answers to the same questions.
+    /// `paths` names the answers holding a path, see [`path_answers`].
+    pub fn record(
+        &mut self,
+        source: &str,
+        blueprint: &str,
+        answers: IndexMap<String, toml::Value>,
+        paths: Vec<String>,
+    ) {
+        self.kopye.paths.retain(|name| !answers.contains_key(name));
+        self.kopye.paths.extend(paths);
+        self.answers.extend(answers);
+
+        self.kopye.source = Some(source.to_string());
+        if !self.kopye.blueprints.iter().any(|name| name == blueprint) {
+            self.kopye.blueprints.push(blueprint.to_string());
+        }
+    }
+
+    /// The contents of the answers file of the project at `destination`.
+    pub fn to_bytes(&self, destination: &Path) -> Result<Vec<u8>, AnswersError> {
+        let root = project_root(destination);
+
+        let mut file = self.clone();
+        file.map_paths(|path| match path.strip_prefix(&root) {
+            Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(relative) => relative.to_path_buf(),
+            // paths outside of the project are kept as they are
+            Err(_) => path.to_path_buf(),
+        });
+
+        let content = toml::to_string_pretty(&file).map_err(|source| AnswersError::Serialize {
+            blueprint: self.kopye.blueprints.join(", "),
+            source,
+        })?;
+
+        Ok(format!("{}{}", HEADER, content).into_bytes())
+    }
+
+    /// Rewrites every path answer with `map`.
+    fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        for name in &self.kopye.paths {
+            if let Some(toml::Value::String(value)) = self.answers.get_mut(name) {
+                *value = map(Path::new(value)).display().to_string();
+            }
+        }
+    }
+}
+
+/// The absolute form of `destination`, which recorded paths are relative to.
+fn project_root(destination: &Path) -> PathBuf {
+    let absolute = std::path::absolute(destination).unwrap_or_else(|_| destination.to_path_buf());
+
+    normalize_path(&absolute.display().to_string())
+}
+
+/// The names of the `persisted` answers that hold a path, i.e. answer a `Path` question.
+pub fn path_answers(
+    questions: &IndexMap<String, Question>,
+    persisted: &IndexMap<String, toml::Value>,
+) -> Vec<String> {
+    persisted
+        .keys()
+        .filter(|name| {
+            questions
+                .get(*name)
+                .is_some_and(|question| matches!(question.r#type, QuestionType::Path))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The answers worth remembering: those of prompted questions, except secrets. Computed values
+/// are left out as well, since they follow from the other answers.
+pub fn persisted(
+    questions: &IndexMap<String, Question>,
+    answers: &IndexMap<String, Answer>,
+) -> IndexMap<String, toml::Value> {
+    answers
+        .iter()
+        .filter(|(name, _)| {
+            questions
+                .get(*name)
+                .is_some_and(|question| !matches!(question.r#type, QuestionType::Computed))
+        })
+        .filter_map(|(name, answer)| {
+            let value = match answer {
+                Answer::String(value) => toml::Value::String(value.clone()),
+                Answer::Bool(value) => toml::Value::Boolean(*value),
+                Answer::Array(values) => {
+                    toml::Value::Array(values.iter().cloned().map(toml::Value::String).collect())
+                }
+                Answer::Value(value) => toml::Value::try_from(value).ok()?,
+                Answer::Secret(_) => return None,
+            };
+
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::QuestionsFile;
+
+    #[test]
+    fn test_answers_file_skips_secrets_and_round_trips() {
+        let questions = QuestionsFile::parse(
+            r#"
+            version = 1
+
+            [questions.name]
+            type = "string"
+
+            [questions.token]
+            type = "password"
+
+            [questions.slug]
+            type = "computed"
+            value = "{{ name }}"
+
+            [questions.docs]
+            type = "path"
+
+            [questions.license]
+            type = "path"
+            "#,
+        )
+        .unwrap();
+
+        let directory = tempfile::tempdir().unwrap();
+        let docs = project_root(directory.path()).join("docs");
+        let license = project_root(Path::new("/elsewhere/LICENSE"));
+        let answers = IndexMap::from([
+            (String::from("name"), Answer::String(String::from("demo"))),
+            (
+                String::from("token"),
+                Answer::Secret(String::from("hunter2").into()),
+            ),
+            (String::from("slug"), Answer::String(String::from("demo"))),
+            (
+                String::from("docs"),
+                Answer::String(docs.display().to_string()),
+            ),
+            (
+                String::from("license"),
+                Answer::String(license.display().to_string()),
+            ),
+        ]);
+
+        let mut file = AnswersFile::default();
+        let remembered = persisted(&questions.questions, &answers);
+        let paths = path_answers(&questions.questions, &remembered);
+        file.record("gh:acme/blueprints", "rust", remembered, paths);
+        file.record(
+            "gh:acme/blueprints",
+            "nix-flake",
+            IndexMap::new(),
+            Vec::new(),
+        );
+
+        let bytes = file.to_bytes(directory.path()).unwrap();
+        fs::write(directory.path().join(ANSWERS_FILE), &bytes).unwrap();
+        let loaded = AnswersFile::load(directory.path()).unwrap();
+
+        let names = loaded.answers.keys().map(String::as_str);
+        assert_eq!(names.collect::<Vec<_>>(), ["name", "docs", "license"]);
+
+        // paths within the project do not depend on where it is checked out
+        let content = String::from_utf8(bytes).unwrap();
+        assert!(content.contains("docs = \"docs\""));
+        assert_eq!(
+            loaded.answers["docs"].as_str(),
+            Some(docs.display().to_string().as_str())
+        );
+        assert_eq!(
+            loaded.answers["license"].as_str(),
+            Some(license.display().to_string().as_str())
+        );
+        assert_eq!(loaded.kopye.blueprints, ["rust", "nix-flake"]);
+        assert_eq!(loaded.kopye.source.as_deref(), Some("gh:acme/blueprints"));
+    }
+}
@@ -1,9 +1,13 @@
 use crate::{
-    prompt,
+    extract, filters, lint, prompt, scaffold, snapshot,
     source::{self, Source},
     template,
 };
 
+pub use crate::lint::LintFormat;
+pub use crate::snapshot::TestOptions;
+pub use crate::template::{RenderOptions, UndefinedBehavior};
+
 #[derive(Debug, thiserror::Error, miette::Diagnostic)]
 pub enum KopyeError {
     #[error(transparent)]
@@ -17,9 +21,26 @@ pub enum KopyeError {
     #[error(transparent)]
     #[diagnostic(transparent)]
     Prompt(#[from] prompt::PromptError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Lint(#[from] lint::LintError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Snapshot(#[from] snapshot::SnapshotError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Scaffold(#[from] scaffold::ScaffoldError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Extract(#[from] extract::ExtractError),
 }
 
-/// Copies a template from the specified source directory to the provided destination path.
+/// Copies templates from the specified source directory to the provided destination path: a
+/// blueprint, or an add-on applied to an existing project, followed by any number of add-ons.
 ///
 /// # Errors
 ///
@@ -27,9 +48,17 @@ pub enum KopyeError {
 ///
 /// - The configuration could not be built from the `source`.
 /// - The template or its files cannot be located or read.
+/// - A template after the first is not an add-on, or an add-on comes first but the destination
+///   does not exist.
 /// - A directory or file cannot be created or written to.
+/// - A template references a variable no question defines, in [`UndefinedBehavior::Strict`] mode.
 /// - Tera fails to initialize or render a template.
-pub fn copy_template(src: &str, template: &str, destination: &str) -> Result<(), KopyeError> {
+pub fn copy_template(
+    src: &str,
+    templates: &[String],
+    destination: &str,
+    options: &RenderOptions,
+) -> Result<(), KopyeError> {
     let source = Source::build_from(src)?;
 
     log::debug!(
@@ -37,7 +66,7 @@ pub fn copy_template(src: &str, template: &str, destination: &str) -> Result<(),
         source.source_dir.display()
     );
 
-    template::try_render(source, template, destination)?;
+    template::try_render(source, templates, destination, options)?;
 
     Ok(())
 }
@@ -56,15 +85,112 @@ pub fn copy_template(src: &str, template: &str, destination: &str) -> Result<(),
 /// - User prompts fail or the user cancels the input.
 /// - The template or its files cannot be located or read.
 /// - A directory or file cannot be created or written to.
+/// - A template references a variable no question defines, in [`UndefinedBehavior::Strict`] mode.
 /// - Tera fails to initialize or render a template.
-pub fn list_templates(src: &str) -> Result<(), KopyeError> {
+pub fn list_templates(src: &str, options: &RenderOptions) -> Result<(), KopyeError> {
     let source = Source::build_from(src)?;
 
     let template = prompt::get_project(source.clone())?;
 
     let destination = prompt::get_destination()?;
 
-    template::try_render(source, &template, &destination)?;
+    template::try_render(source, &[template], &destination, options)?;
+
+    Ok(())
+}
+
+/// Prints the filters and functions kopye makes available to blueprint templates.
+pub fn list_filters() {
+    filters::print_docs();
+}
+
+/// Checks every blueprint of a source for mistakes that would otherwise only surface while
+/// generating a project, and prints the findings in the given `format`.
+///
+/// # Errors
+///
+/// Returns a [`KopyeError`] if:
+///
+/// - The configuration could not be built from the `source`.
+/// - Any finding is an error, e.g. a missing blueprint path, a dependency cycle or an undefined
+///   variable. Warnings alone do not fail.
+pub fn lint_source(src: &str, format: LintFormat) -> Result<(), KopyeError> {
+    let source = Source::build_from(src)?;
+
+    lint::lint_source(&source).finish(format)?;
+
+    Ok(())
+}
+
+/// Renders every blueprint of a source with the answer sets in its own `tests/*.toml`, without
+/// prompting, and compares the output with the snapshots in its `tests/snapshots/<case>/`.
+///
+/// # Errors
+///
+/// Returns a [`KopyeError`] if:
+///
+/// - The configuration could not be built from the `source`.
+/// - A rendered blueprint differs from its snapshot, fails to render, or fails
+///   [`TestOptions::command`]. With [`TestOptions::update`], snapshots are rewritten instead.
+pub fn test_blueprints(src: &str, options: &TestOptions) -> Result<(), KopyeError> {
+    let source = Source::build_from(src)?;
+
+    snapshot::test_source(&source, options)?;
+
+    Ok(())
+}
+
+/// Creates a new blueprint source in `directory`, with an example blueprint and its tests.
+///
+/// # Errors
+///
+/// Returns a [`KopyeError`] if:
+///
+/// - `directory` already holds a `blueprints.toml`.
+/// - A directory or file cannot be created or written to.
+pub fn init_source(directory: &str) -> Result<(), KopyeError> {
+    scaffold::init_source(std::path::Path::new(directory))?;
+
+    Ok(())
+}
+
+/// Registers a blueprint named `name` in the source found in `directory`, pointing to `path`
+/// (defaulting to `name`), and creates the blueprint when its directory does not exist yet.
+///
+/// # Errors
+///
+/// Returns a [`KopyeError`] if:
+///
+/// - `blueprints.toml` cannot be read or parsed, or already lists `name`.
+/// - A directory or file cannot be created or written to.
+pub fn add_blueprint(directory: &str, name: &str, path: Option<&str>) -> Result<(), KopyeError> {
+    scaffold::add_blueprint(std::path::Path::new(directory), name, path)?;
+
+    Ok(())
+}
+
+/// Turns the project in `project` into a blueprint written to `output`, templating the values of
+/// the given `name=value` variables.
+///
+/// # Errors
+///
+/// Returns a [`KopyeError`] if:
+///
+/// - A variable is not of the form `name=value`.
+/// - `project` is not a directory, or `output` is a non-empty directory.
+/// - A file cannot be read, or a directory or file cannot be created or written to.
+pub fn extract_blueprint(
+    project: &str,
+    output: &str,
+    variables: &[String],
+) -> Result<(), KopyeError> {
+    let variables = extract::parse_variables(variables)?;
+
+    extract::extract_blueprint(
+        std::path::Path::new(project),
+        std::path::Path::new(output),
+        &variables,
+    )?;
 
     Ok(())
 }
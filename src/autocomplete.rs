@@ -0,0 +1,101 @@
+use inquire::{autocompletion::Replacement, Autocomplete, CustomUserError};
+use std::path::{Path, PathBuf};
+
+/// Completes filesystem paths typed into a prompt, relative to a `base` directory.
+///
+/// Directories are suggested with a trailing `/` so that completion can continue into them.
+#[derive(Debug, Clone)]
+pub struct PathCompleter {
+    pub base: PathBuf,
+    /// Only suggest directories, e.g. for `kind = "dir"`
+    pub directories_only: bool,
+}
+impl PathCompleter {
+    /// Splits `input` into the directory to list and the partial file name to match.
+    fn split_input(input: &str) -> (&str, &str) {
+        match input.rfind('/') {
+            Some(index) => (&input[..=index], &input[index + 1..]),
+            None => ("", input),
+        }
+    }
+
+    fn resolve(&self, directory: &str) -> PathBuf {
+        let path = Path::new(directory);
+
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.base.join(path)
+        }
+    }
+}
+impl Autocomplete for PathCompleter {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        let (directory, partial) = PathCompleter::split_input(input);
+
+        let Ok(entries) = std::fs::read_dir(self.resolve(directory)) else {
+            return Ok(Vec::new());
+        };
+
+        let mut suggestions = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+
+                // hidden entries are only suggested once a `.` has been typed
+                if !name.starts_with(partial) || (name.starts_with('.') && partial.is_empty()) {
+                    return None;
+                }
+
+                if is_dir {
+                    Some(format!("{}{}/", directory, name))
+                } else if self.directories_only {
+                    None
+                } else {
+                    Some(format!("{}{}", directory, name))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        suggestions.sort();
+
+        Ok(suggestions)
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        if highlighted_suggestion.is_some() {
+            return Ok(highlighted_suggestion);
+        }
+
+        // complete up to the longest prefix shared by every suggestion
+        let suggestions = self.get_suggestions(input)?;
+        let Some(first) = suggestions.first() else {
+            return Ok(None);
+        };
+
+        let prefix = suggestions
+            .iter()
+            .skip(1)
+            .fold(first.as_str(), |prefix, suggestion| {
+                let shared = prefix
+                    .char_indices()
+                    .zip(suggestion.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map(|((index, _), _)| index)
+                    .unwrap_or(prefix.len().min(suggestion.len()));
+
+                &prefix[..shared]
+            });
+
+        if prefix.len() > input.len() {
+            Ok(Some(prefix.to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+}
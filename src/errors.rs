@@ -9,6 +9,10 @@ pub enum FileOperation {
     Write,
     #[error("creating a directory")]
     Mkdir,
+    #[error("removing a directory")]
+    Remove,
+    #[error("running a command")]
+    Execute,
 }
 #[derive(Debug, Error, Diagnostic)]
 #[error("I/O error: {operation} on path '{path}'")]
@@ -44,14 +48,14 @@ pub struct ParseError {
     pub file_format: FileFormat,
     pub path: std::path::PathBuf,
     #[source]
-    pub source: toml::de::Error,
+    pub source: Box<toml::de::Error>,
 }
 impl ParseError {
     pub fn new(file_format: FileFormat, path: std::path::PathBuf, error: toml::de::Error) -> Self {
         Self {
             file_format,
             path,
-            source: error,
+            source: Box::new(error),
         }
     }
 }
@@ -0,0 +1,369 @@
+use crate::{
+    errors::{FileOperation, IoError},
+    filters,
+};
+use colored::Colorize;
+use indexmap::IndexMap;
+use miette::Diagnostic;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum ExtractError {
+    #[error("I/O error within extract domain")]
+    #[diagnostic(code(kopye::extract::io))]
+    Io(#[from] IoError),
+
+    #[error("Unable to walk '{path}'")]
+    #[diagnostic(code(kopye::extract::walk))]
+    Walk {
+        path: PathBuf,
+        #[source]
+        source: ignore::Error,
+    },
+
+    #[error("Invalid variable `{input}`, expected `name=value`")]
+    #[diagnostic(
+        code(kopye::extract::invalid_variable),
+        help("Pass variables as e.g. --var project=myapp")
+    )]
+    InvalidVariable { input: String },
+
+    #[error("'{path}' already exists and is not empty")]
+    #[diagnostic(
+        code(kopye::extract::output_exists),
+        help("Choose another output directory with --output")
+    )]
+    OutputExists { path: PathBuf },
+
+    #[error("'{path}' is not a directory")]
+    #[diagnostic(code(kopye::extract::not_a_directory))]
+    NotADirectory { path: PathBuf },
+}
+
+/// Filters whose output is looked for in the project, besides the value itself.
+const CASE_FILTERS: [&str; 5] = [
+    "snake_case",
+    "kebab_case",
+    "camel_case",
+    "pascal_case",
+    "screaming_snake_case",
+];
+
+/// Tera delimiters that must be escaped once a file becomes a template.
+const DELIMITERS: [&str; 3] = ["{{", "{%", "{#"];
+
+const TERA_FILE_EXTENSION: &str = "tera";
+
+/// Replaces project-specific values, and their case variants, with tera expressions.
+struct Templater {
+    /// Every searched string and the expression replacing it, longest first
+    replacements: Vec<(String, String)>,
+    pattern: regex::Regex,
+}
+impl Templater {
+    fn new(variables: &IndexMap<String, String>) -> Self {
+        let mut tera = tera::Tera::default();
+        filters::register(&mut tera);
+
+        let mut replacements: Vec<(String, String)> = Vec::new();
+
+        for (name, value) in variables {
+            let mut context = tera::Context::new();
+            context.insert("value", value);
+
+            let variants = CASE_FILTERS.iter().filter_map(|filter| {
+                let template = format!("{{{{ value | {} }}}}", filter);
+                let variant = tera.render_str(&template, &context).ok()?;
+
+                Some((variant, format!("{{{{ {} | {} }}}}", name, filter)))
+            });
+
+            for (variant, expression) in
+                std::iter::once((value.clone(), format!("{{{{ {} }}}}", name))).chain(variants)
+            {
+                // the first variable claiming a string keeps it, e.g. the value over its variants
+                if !variant.is_empty() && !replacements.iter().any(|(text, _)| *text == variant) {
+                    replacements.push((variant, expression));
+                }
+            }
+        }
+
+        for delimiter in DELIMITERS {
+            replacements.push((
+                delimiter.to_string(),
+                format!("{{{{ \"{}\" }}}}", delimiter),
+            ));
+        }
+
+        // the longest candidate wins when several start at the same position
+        replacements.sort_by_key(|(text, _)| std::cmp::Reverse(text.len()));
+
+        let alternatives = replacements
+            .iter()
+            .map(|(text, _)| regex::escape(text))
+            .collect::<Vec<_>>()
+            .join("|");
+        let pattern = regex::Regex::new(&alternatives).expect("escaped alternatives are valid");
+
+        Self {
+            replacements,
+            pattern,
+        }
+    }
+
+    /// Replaces every value and delimiter in `input`, telling whether any value was found.
+    ///
+    /// Values only match whole words: `app` matches in `app_state` or `app-cli`, but neither in
+    /// `happy` nor in `mapping`.
+    fn replace(&self, input: &str) -> (String, bool) {
+        let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+
+        let mut found = false;
+        let mut replaced = String::with_capacity(input.len());
+        let mut position = 0;
+
+        while let Some(matched) = self.pattern.find_at(input, position) {
+            let text = matched.as_str();
+            let (before, after) = (&input[..matched.start()], &input[matched.end()..]);
+
+            let is_delimiter = DELIMITERS.contains(&text);
+            let starts_inside = is_word(text.chars().next()) && is_word(before.chars().next_back());
+            let ends_inside = is_word(text.chars().next_back()) && is_word(after.chars().next());
+
+            if !is_delimiter && (starts_inside || ends_inside) {
+                // a shorter value may still start further in the word
+                let next = matched.start() + text.chars().next().map_or(1, char::len_utf8);
+                replaced.push_str(&input[position..next]);
+                position = next;
+
+                continue;
+            }
+
+            let (_, expression) = self
+                .replacements
+                .iter()
+                .find(|(candidate, _)| candidate == text)
+                .expect("every match is a replacement");
+
+            found |= !is_delimiter;
+
+            replaced.push_str(&input[position..matched.start()]);
+            replaced.push_str(expression);
+            position = matched.end();
+        }
+
+        replaced.push_str(&input[position..]);
+
+        (replaced, found)
+    }
+
+    /// Returns `input` as a template, or `None` when it holds none of the values.
+    fn template(&self, input: &str) -> Option<String> {
+        let (templated, found) = self.replace(input);
+
+        found.then_some(templated)
+    }
+
+    /// Templates `input` even without values, so that existing delimiters are escaped.
+    fn escape(&self, input: &str) -> String {
+        self.replace(input).0
+    }
+}
+
+/// Parses `name=value` pairs, as given to `--var`.
+pub fn parse_variables(inputs: &[String]) -> Result<IndexMap<String, String>, ExtractError> {
+    inputs
+        .iter()
+        .map(|input| {
+            input
+                .split_once('=')
+                .filter(|(name, value)| !name.trim().is_empty() && !value.is_empty())
+                .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+                .ok_or_else(|| ExtractError::InvalidVariable {
+                    input: input.clone(),
+                })
+        })
+        .collect()
+}
+
+/// A file is treated as binary, and copied as-is, when it is not UTF-8 or contains a NUL byte.
+fn read_text(bytes: &[u8]) -> Option<&str> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .filter(|text| !text.contains('\0'))
+}
+
+/// Copies `project` into a new blueprint in `output`.
+///
+/// Occurrences of every variable's value, and of its case variants, become tera expressions, in
+/// file contents as well as in path segments. Files ignored by git are skipped, binary files are
+/// copied unchanged, and a `blueprint.toml` asks one question per variable.
+pub fn extract_blueprint(
+    project: &Path,
+    output: &Path,
+    variables: &IndexMap<String, String>,
+) -> Result<(), ExtractError> {
+    if !project.is_dir() {
+        return Err(ExtractError::NotADirectory {
+            path: project.to_path_buf(),
+        });
+    }
+
+    let is_empty = fs::read_dir(output)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(true);
+    if !is_empty {
+        return Err(ExtractError::OutputExists {
+            path: output.to_path_buf(),
+        });
+    }
+
+    fs::create_dir_all(output)
+        .map_err(|error| IoError::new(FileOperation::Mkdir, output.into(), error))?;
+    let output_directory = output.canonicalize().ok();
+
+    let templater = Templater::new(variables);
+
+    // `output` may well be inside `project`, e.g. `kopye extract . -o blueprint`
+    let walker = ignore::WalkBuilder::new(project)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(move |entry| {
+            entry.file_name() != ".git" && entry.path().canonicalize().ok() != output_directory
+        })
+        .build();
+
+    for entry in walker {
+        let entry = entry.map_err(|error| ExtractError::Walk {
+            path: project.to_path_buf(),
+            source: error,
+        })?;
+
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(project).unwrap_or(entry.path());
+
+        let mut destination = relative
+            .components()
+            .map(|component| templater.escape(&component.as_os_str().to_string_lossy()))
+            .collect::<PathBuf>();
+
+        let bytes = fs::read(entry.path())
+            .map_err(|error| IoError::new(FileOperation::Read, entry.path().into(), error))?;
+
+        let contents = match read_text(&bytes) {
+            Some(text) => {
+                let is_tera = relative
+                    .extension()
+                    .is_some_and(|ext| ext == TERA_FILE_EXTENSION);
+
+                // existing `.tera` files must keep their extension once rendered
+                let templated = match templater.template(text) {
+                    Some(templated) => Some(templated),
+                    None if is_tera => Some(templater.escape(text)),
+                    None => None,
+                };
+
+                match templated {
+                    Some(templated) => {
+                        let mut name = destination.file_name().unwrap_or_default().to_owned();
+                        name.push(format!(".{}", TERA_FILE_EXTENSION));
+                        destination.set_file_name(name);
+
+                        templated.into_bytes()
+                    }
+                    None => bytes,
+                }
+            }
+            None => bytes,
+        };
+
+        write(&output.join(destination), &contents)?;
+    }
+
+    write(
+        &output.join("blueprint.toml"),
+        blueprint_toml(variables).as_bytes(),
+    )?;
+
+    Ok(())
+}
+
+/// A versioned `blueprint.toml` with a text question per variable.
+fn blueprint_toml(variables: &IndexMap<String, String>) -> String {
+    let mut document = toml_edit::DocumentMut::new();
+    document.insert("version", toml_edit::value(1));
+
+    let mut questions = toml_edit::Table::new();
+    questions.set_implicit(true);
+
+    for (name, value) in variables {
+        let mut question = toml_edit::Table::new();
+        question.insert("type", toml_edit::value("string"));
+        question.insert(
+            "help",
+            toml_edit::value(format!("{} (extracted from \"{}\")", name, value)),
+        );
+
+        questions.insert(name, toml_edit::Item::Table(question));
+    }
+
+    document.insert("questions", toml_edit::Item::Table(questions));
+
+    document.to_string()
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<(), IoError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|error| IoError::new(FileOperation::Mkdir, parent.into(), error))?;
+    }
+
+    fs::write(path, contents)
+        .map_err(|error| IoError::new(FileOperation::Write, path.into(), error))?;
+
+    println!("{} {}", "create".green(), path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templater_replaces_values_and_case_variants() {
+        let variables = IndexMap::from([(String::from("project"), String::from("my-app"))]);
+        let templater = Templater::new(&variables);
+
+        assert_eq!(
+            templater.template("use my_app::MyApp; // my-app {{ x }}"),
+            Some(String::from(
+                "use {{ project | snake_case }}::{{ project | pascal_case }}; \
+                 // {{ project }} {{ \"{{\" }} x }}"
+            ))
+        );
+        assert_eq!(templater.template("nothing {% here %}"), None);
+
+        let variables = IndexMap::from([(String::from("project"), String::from("app"))]);
+        let templater = Templater::new(&variables);
+        assert_eq!(
+            templater.template("app: application, happy mapping, app_state, App.{{"),
+            Some(String::from(
+                "{{ project }}: application, happy mapping, {{ project }}_state, \
+                 {{ project | pascal_case }}.{{ \"{{\" }}"
+            ))
+        );
+        assert_eq!(templater.template("happy mapping"), None);
+        assert_eq!(
+            templater.escape("nothing {% here %}"),
+            "nothing {{ \"{%\" }} here %}"
+        );
+    }
+}
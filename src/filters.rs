@@ -0,0 +1,392 @@
+use colored::Colorize;
+use std::{collections::HashMap, fmt::Write};
+use tera::{Error, Result, Tera, Value};
+
+/// Documentation for every filter available to blueprints, as `(usage, description)`.
+pub const FILTERS: [(&str, &str); 8] = [
+    ("snake_case", "\"My Project\" -> \"my_project\""),
+    ("kebab_case", "\"My Project\" -> \"my-project\""),
+    ("camel_case", "\"my project\" -> \"myProject\""),
+    ("pascal_case", "\"my project\" -> \"MyProject\""),
+    ("screaming_snake_case", "\"my project\" -> \"MY_PROJECT\""),
+    (
+        "crate_name",
+        "\"My-Project 2\" -> \"my_project_2\", a valid crate and Rust identifier",
+    ),
+    (
+        "slugify",
+        "\"My Project!\" -> \"my-project\" (built into tera)",
+    ),
+    (
+        "pluralize",
+        "\"service\" -> \"services\"; on numbers, tera's own `pluralize(singular, plural)`",
+    ),
+];
+
+/// Documentation for every function available to blueprints, as `(usage, description)`.
+pub const FUNCTIONS: [(&str, &str); 4] = [
+    ("uuid()", "a random (v4) UUID"),
+    (
+        "now(format, utc, timestamp)",
+        "current date/time, as tera's own `now`, or formatted with strftime syntax, e.g. now(format=\"%Y\")",
+    ),
+    (
+        "env(name, default)",
+        "value of an environment variable, failing when unset without a default",
+    ),
+    (
+        "git_config(key, default)",
+        "value from git config, e.g. git_config(key=\"user.name\")",
+    ),
+];
+
+/// Registers kopye's filters and functions on a [`Tera`] instance.
+pub fn register(tera: &mut Tera) {
+    tera.register_filter("snake_case", case_filter(|words| join_lower(words, "_")));
+    tera.register_filter("kebab_case", case_filter(|words| join_lower(words, "-")));
+    tera.register_filter("camel_case", case_filter(camel_case));
+    tera.register_filter("pascal_case", case_filter(pascal_case));
+    tera.register_filter(
+        "screaming_snake_case",
+        case_filter(|words| join_lower(words, "_").to_uppercase()),
+    );
+    tera.register_filter("crate_name", case_filter(crate_name));
+    tera.register_filter("pluralize", pluralize);
+
+    tera.register_function("uuid", uuid);
+    tera.register_function("now", now);
+    tera.register_function("env", env);
+    tera.register_function("git_config", git_config);
+}
+
+/// Prints the documentation for kopye's filters and functions.
+pub fn print_docs() {
+    println!("{}", "Filters".bold().bright_blue());
+    for (usage, description) in FILTERS {
+        println!("  {:<24} {}", usage.green(), description);
+    }
+
+    println!("\n{}", "Functions".bold().bright_blue());
+    for (usage, description) in FUNCTIONS {
+        println!("  {:<24} {}", usage.green(), description);
+    }
+}
+
+/// Splits a string into lowercase words on separators and case boundaries,
+/// e.g. `"HTTPServer_v2 name"` -> `["http", "server", "v2", "name"]`.
+pub fn split_words(input: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = input.chars().collect();
+
+    for (index, &ch) in chars.iter().enumerate() {
+        if !ch.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        if ch.is_uppercase() && !current.is_empty() {
+            let previous = chars[index - 1];
+            let next_is_lower = chars.get(index + 1).is_some_and(|c| c.is_lowercase());
+
+            // "myProject" -> my|Project, "HTTPServer" -> HTTP|Server
+            if previous.is_lowercase()
+                || previous.is_numeric()
+                || (previous.is_uppercase() && next_is_lower)
+            {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+
+        current.extend(ch.to_lowercase());
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+fn join_lower(words: Vec<String>, separator: &str) -> String {
+    words.join(separator)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn pascal_case(words: Vec<String>) -> String {
+    words.iter().map(|word| capitalize(word)).collect()
+}
+
+fn camel_case(words: Vec<String>) -> String {
+    let mut words = words.into_iter();
+
+    match words.next() {
+        Some(first) => first + &pascal_case(words.collect()),
+        None => String::new(),
+    }
+}
+
+fn crate_name(words: Vec<String>) -> String {
+    let name = words
+        .iter()
+        .map(|word| {
+            word.chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+
+    // identifiers cannot start with a digit
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Wraps a word-based case conversion into a tera filter over strings.
+fn case_filter(
+    convert: impl Fn(Vec<String>) -> String + Sync + Send,
+) -> impl Fn(&Value, &HashMap<String, Value>) -> Result<Value> + Sync + Send {
+    move |value, _args| {
+        let input = value
+            .as_str()
+            .ok_or_else(|| Error::msg(format!("Filter expected a string, received: {}", value)))?;
+
+        Ok(Value::String(convert(split_words(input))))
+    }
+}
+
+fn string_arg(args: &HashMap<String, Value>, name: &str) -> Result<Option<String>> {
+    match args.get(name) {
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(other) => Err(Error::msg(format!(
+            "Argument `{}` must be a string, received: {}",
+            name, other
+        ))),
+        None => Ok(None),
+    }
+}
+
+fn required_string_arg(
+    args: &HashMap<String, Value>,
+    function: &str,
+    name: &str,
+) -> Result<String> {
+    string_arg(args, name)?.ok_or_else(|| {
+        Error::msg(format!(
+            "Function `{}` requires a `{}` argument",
+            function, name
+        ))
+    })
+}
+
+/// Pluralizes an English word, or behaves like tera's `pluralize` when given a number, which it
+/// replaces. `suffix` is accepted for `plural`, as in earlier tera versions.
+fn pluralize(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    if let Some(count) = value.as_f64() {
+        let singular = string_arg(args, "singular")?.unwrap_or_default();
+        let plural = match string_arg(args, "plural")? {
+            Some(plural) => plural,
+            None => string_arg(args, "suffix")?.unwrap_or_else(|| String::from("s")),
+        };
+
+        return if (count.abs() - 1.).abs() > f64::EPSILON {
+            Ok(Value::String(plural))
+        } else {
+            Ok(Value::String(singular))
+        };
+    }
+
+    let word = value.as_str().ok_or_else(|| {
+        Error::msg(format!(
+            "Filter `pluralize` expected a string or number, received: {}",
+            value
+        ))
+    })?;
+
+    let lower = word.to_lowercase();
+    let plural = if lower.ends_with('s')
+        || lower.ends_with('x')
+        || lower.ends_with('z')
+        || lower.ends_with("ch")
+        || lower.ends_with("sh")
+    {
+        format!("{}es", word)
+    } else if lower.ends_with('y')
+        && !lower.ends_with("ay")
+        && !lower.ends_with("ey")
+        && !lower.ends_with("oy")
+        && !lower.ends_with("uy")
+    {
+        format!("{}ies", &word[..word.len() - 1])
+    } else if word.is_empty() {
+        String::new()
+    } else {
+        format!("{}s", word)
+    };
+
+    Ok(Value::String(plural))
+}
+
+fn uuid(_args: &HashMap<String, Value>) -> Result<Value> {
+    let mut bytes: [u8; 16] = rand::random();
+    // version 4, variant RFC 4122
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    Ok(Value::String(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )))
+}
+
+/// Tera's own `now`, i.e. an RFC 3339 date/time or a `timestamp`, which it replaces, extended with
+/// a strftime `format`.
+fn now(args: &HashMap<String, Value>) -> Result<Value> {
+    let flag = |name: &str| match args.get(name) {
+        Some(Value::Bool(flag)) => Ok(*flag),
+        Some(other) => Err(Error::msg(format!(
+            "Function `now` received {}={} but `{}` can only be a boolean",
+            name, other, name
+        ))),
+        None => Ok(false),
+    };
+    let (utc, timestamp) = (flag("utc")?, flag("timestamp")?);
+    let local = chrono::Local::now();
+    let utc_now = local.with_timezone(&chrono::Utc);
+
+    if timestamp {
+        return Ok(Value::from(local.timestamp()));
+    }
+
+    let Some(format) = string_arg(args, "format")? else {
+        return Ok(Value::String(match utc {
+            true => utc_now.to_rfc3339(),
+            false => local.to_rfc3339(),
+        }));
+    };
+
+    let mut formatted = String::new();
+    let written = match utc {
+        true => write!(formatted, "{}", utc_now.format(&format)),
+        false => write!(formatted, "{}", local.format(&format)),
+    };
+
+    // chrono reports unknown specifiers as a formatting error
+    written.map_err(|_| {
+        Error::msg(format!(
+            "Function `now` received an invalid format: {}",
+            format
+        ))
+    })?;
+
+    Ok(Value::String(formatted))
+}
+
+fn env(args: &HashMap<String, Value>) -> Result<Value> {
+    let name = required_string_arg(args, "env", "name")?;
+
+    match (std::env::var(&name), string_arg(args, "default")?) {
+        (Ok(value), _) => Ok(Value::String(value)),
+        (Err(_), Some(default)) => Ok(Value::String(default)),
+        (Err(error), None) => Err(Error::msg(format!(
+            "Environment variable `{}` is not available: {}",
+            name, error
+        ))),
+    }
+}
+
+fn git_config(args: &HashMap<String, Value>) -> Result<Value> {
+    let key = required_string_arg(args, "git_config", "key")?;
+
+    let value = git2::Config::open_default().and_then(|config| config.get_string(&key));
+
+    match (value, string_arg(args, "default")?) {
+        (Ok(value), _) => Ok(Value::String(value)),
+        (Err(_), Some(default)) => Ok(Value::String(default)),
+        (Err(error), None) => Err(Error::msg(format!(
+            "Unable to read `{}` from git config: {}",
+            key,
+            error.message()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(input: &str) -> String {
+        let mut tera = Tera::default();
+        register(&mut tera);
+
+        tera.render_str(input, &tera::Context::new()).unwrap()
+    }
+
+    #[test]
+    fn test_case_filters() {
+        assert_eq!(
+            render("{{ 'My HTTPServer' | snake_case }}"),
+            "my_http_server"
+        );
+        assert_eq!(render("{{ 'my_project' | kebab_case }}"), "my-project");
+        assert_eq!(render("{{ 'my-project' | camel_case }}"), "myProject");
+        assert_eq!(render("{{ 'my project' | pascal_case }}"), "MyProject");
+        assert_eq!(
+            render("{{ 'myProject' | screaming_snake_case }}"),
+            "MY_PROJECT"
+        );
+        assert_eq!(render("{{ '2-Fast cli!' | crate_name }}"), "_2_fast_cli");
+    }
+
+    #[test]
+    fn test_pluralize() {
+        assert_eq!(render("{{ 'service' | pluralize }}"), "services");
+        assert_eq!(render("{{ 'policy' | pluralize }}"), "policies");
+        assert_eq!(render("{{ 'box' | pluralize }}"), "boxes");
+        assert_eq!(render("{{ 2 | pluralize }}"), "s");
+        assert_eq!(
+            render("{{ 1 | pluralize(singular='y', plural='ies') }}"),
+            "y"
+        );
+        assert_eq!(render("{{ 3 | pluralize(suffix='es') }}"), "es");
+    }
+
+    #[test]
+    fn test_now_keeps_tera_arguments() {
+        let timestamp = render("{{ now(timestamp=true) }}");
+        assert!(timestamp.parse::<i64>().unwrap() > 0);
+
+        let utc = render("{{ now(utc=true) }}");
+        assert!(chrono::DateTime::parse_from_rfc3339(&utc).is_ok());
+        assert!(utc.ends_with("+00:00"));
+
+        assert_eq!(render("{{ now(format='%Y') }}").len(), 4);
+        assert_eq!(
+            render("{% set t = now(timestamp=true) %}{% if t is number %}yes{% endif %}"),
+            "yes"
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use crate::errors::{FileOperation, IoError};
+use colored::Colorize;
+use git2::{IndexEntry, IndexTime, Oid, Repository};
+use inquire::{Confirm, InquireError};
+use miette::Diagnostic;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum GitError {
+    #[error("I/O error within git domain")]
+    #[diagnostic(code(kopye::git::io))]
+    Io(#[from] IoError),
+
+    #[error("Unable to {action} in '{path}'")]
+    #[diagnostic(
+        code(kopye::git::operation),
+        help("The generated files were kept, commit them by hand or pass `--skip-git`")
+    )]
+    Operation {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: git2::Error,
+    },
+
+    #[error("No git identity is configured to commit the generated files")]
+    #[diagnostic(
+        code(kopye::git::identity),
+        help("Set one with `git config --global user.name <name>` and `git config --global user.email <email>`")
+    )]
+    Identity {
+        #[source]
+        source: git2::Error,
+    },
+
+    #[error("Error occurred trying to confirm the repository to commit to")]
+    #[diagnostic(code(kopye::git::prompt))]
+    Prompt(#[from] InquireError),
+
+    #[error("Branch `{branch}` already exists in '{path}'")]
+    #[diagnostic(
+        code(kopye::git::branch_exists),
+        help("The generated files were kept, change `git.branch` in blueprint.toml or commit them by hand")
+    )]
+    BranchExists { branch: String, path: PathBuf },
+}
+
+/// The `[git]` table of `blueprint.toml`; the generated files are committed when it is present.
+#[derive(Debug, Deserialize, Clone)]
+pub struct GitSettings {
+    /// Commit message, rendered by tera
+    #[serde(default = "GitSettings::default_message")]
+    pub message: String,
+    /// Url of the `origin` remote, rendered by tera; no remote is added when it renders empty
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// Branch the files are committed to when the destination already is a repository, rendered
+    /// by tera
+    #[serde(default = "GitSettings::default_branch")]
+    pub branch: String,
+    /// Whether to commit to a repository enclosing the destination, e.g. a monorepo, rather than
+    /// creating one in the destination. Asked when unset, unless nothing is prompted.
+    #[serde(default)]
+    pub enclosing: Option<bool>,
+}
+impl GitSettings {
+    fn default_message() -> String {
+        String::from(
+            "Generate {{ kopye.destination_name }} from the `{{ kopye.blueprint }}` blueprint",
+        )
+    }
+
+    fn default_branch() -> String {
+        String::from("kopye/{{ kopye.blueprint }}")
+    }
+
+    /// Every tera template of the settings.
+    pub fn templates(&self) -> Vec<String> {
+        [self.message.clone(), self.branch.clone()]
+            .into_iter()
+            .chain(self.remote.clone())
+            .collect()
+    }
+}
+
+/// [`GitSettings`] whose templates were rendered with the answers.
+#[derive(Debug, Clone)]
+pub struct RenderedGit {
+    pub message: String,
+    pub remote: Option<String>,
+    pub branch: String,
+    pub enclosing: Option<bool>,
+}
+
+const REMOTE_NAME: &str = "origin";
+
+fn operation<'a>(
+    action: &'static str,
+    path: &'a Path,
+) -> impl FnOnce(git2::Error) -> GitError + 'a {
+    move |source| GitError::Operation {
+        action,
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// The index mode of a file: executable or regular.
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return 0o100755;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+
+    0o100644
+}
+
+impl RenderedGit {
+    /// Commits the generated `files`, given relative to `destination`.
+    ///
+    /// A destination outside of any repository becomes one, with the files as its initial commit
+    /// and the rendered remote as `origin`. Within an existing repository the files are committed
+    /// to a new branch instead, leaving anything else staged untouched. A repository enclosing
+    /// the destination is only used when [`GitSettings::enclosing`] allows it, or when the user
+    /// agrees to it if `interactive`. Files ignored by git, e.g. a generated `.env`, are never
+    /// committed.
+    pub fn commit(
+        &self,
+        destination: &Path,
+        files: &[PathBuf],
+        interactive: bool,
+    ) -> Result<(), GitError> {
+        let destination = destination
+            .canonicalize()
+            .map_err(|error| IoError::new(FileOperation::Read, destination.into(), error))?;
+
+        let existing = match Repository::discover(&destination) {
+            Ok(repository) if self.reuses(&repository, &destination, interactive)? => {
+                Some(repository)
+            }
+            _ => None,
+        };
+
+        let (repository, created) = match existing {
+            Some(repository) => (repository, false),
+            None => (
+                Repository::init(&destination)
+                    .map_err(operation("initialize a repository", &destination))?,
+                true,
+            ),
+        };
+
+        let workdir = repository
+            .workdir()
+            .unwrap_or(&destination)
+            .canonicalize()
+            .map_err(|error| IoError::new(FileOperation::Read, destination.clone(), error))?;
+
+        let mut paths = Vec::new();
+
+        for file in files {
+            let path = destination
+                .join(file)
+                .strip_prefix(&workdir)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| file.clone());
+
+            let ignored = repository
+                .is_path_ignored(&path)
+                .map_err(operation("check ignored files", &workdir))?;
+
+            if !ignored {
+                paths.push(path);
+            }
+        }
+
+        let signature = repository
+            .signature()
+            .map_err(|source| GitError::Identity { source })?;
+
+        let parent = match repository.head() {
+            Ok(head) => Some(
+                head.peel_to_commit()
+                    .map_err(operation("read the current commit", &workdir))?,
+            ),
+            Err(_) => None,
+        };
+
+        let branch_exists = || GitError::BranchExists {
+            branch: self.branch.clone(),
+            path: workdir.clone(),
+        };
+
+        let reference = match (&parent, created) {
+            (Some(parent), _) => {
+                let branch = repository
+                    .branch(&self.branch, parent, false)
+                    .map_err(|_| branch_exists())?;
+
+                branch.get().name().unwrap_or_default().to_string()
+            }
+            // a new repository starts on its default branch
+            (None, true) => String::from("HEAD"),
+            // an existing repository without commits yet, whose HEAD is still unborn
+            (None, false) => {
+                let name = format!("refs/heads/{}", self.branch);
+
+                if repository.find_reference(&name).is_ok() {
+                    return Err(branch_exists());
+                }
+
+                name
+            }
+        };
+
+        // the branch points at the current commit, so the working tree stays as it is
+        if !created {
+            repository
+                .set_head(&reference)
+                .map_err(operation("switch branch", &workdir))?;
+        }
+
+        let tree = self.write_tree(&repository, &workdir, parent.as_ref(), &paths)?;
+
+        let parents = parent.iter().collect::<Vec<_>>();
+        let commit = repository
+            .commit(
+                Some(&reference),
+                &signature,
+                &signature,
+                &self.message,
+                &tree,
+                &parents,
+            )
+            .map_err(operation("commit the generated files", &workdir))?;
+
+        println!(
+            "{} {} {}",
+            "git".cyan(),
+            &commit.to_string()[..7],
+            self.message.lines().next().unwrap_or_default()
+        );
+
+        if let (true, Some(remote)) = (created, self.remote.as_deref()) {
+            repository
+                .remote(REMOTE_NAME, remote)
+                .map_err(operation("add the remote", &workdir))?;
+
+            println!("{} {} {}", "git".cyan(), REMOTE_NAME, remote);
+        } else if let Some(remote) = &self.remote {
+            log::warn!(
+                "'{}' already is a repository, `{}` was not added as a remote",
+                workdir.display(),
+                remote
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Whether to commit to `repository`, found from `destination`: always when it is the
+    /// repository of the destination itself, as set by [`GitSettings::enclosing`] or asked
+    /// otherwise.
+    fn reuses(
+        &self,
+        repository: &Repository,
+        destination: &Path,
+        interactive: bool,
+    ) -> Result<bool, GitError> {
+        let Some(workdir) = repository
+            .workdir()
+            .and_then(|workdir| workdir.canonicalize().ok())
+        else {
+            return Ok(false);
+        };
+
+        if workdir == destination {
+            return Ok(true);
+        }
+
+        match (self.enclosing, interactive) {
+            (Some(enclosing), _) => Ok(enclosing),
+            (None, false) => Ok(false),
+            (None, true) => {
+                let question = format!(
+                    "'{}' is inside the git repository '{}'. Commit to a new `{}` branch there?",
+                    destination.display(),
+                    workdir.display(),
+                    self.branch
+                );
+
+                let confirmed = Confirm::new(&question)
+                    .with_default(false)
+                    .with_help_message("Otherwise the destination becomes a repository of its own")
+                    .prompt()?;
+
+                Ok(confirmed)
+            }
+        }
+    }
+
+    /// Writes the tree of the current commit with `paths` added, and stages `paths`.
+    fn write_tree<'r>(
+        &self,
+        repository: &'r Repository,
+        workdir: &Path,
+        parent: Option<&git2::Commit>,
+        paths: &[PathBuf],
+    ) -> Result<git2::Tree<'r>, GitError> {
+        // a separate index, so that changes the user staged are not committed along
+        let mut index = git2::Index::new().map_err(operation("create an index", workdir))?;
+
+        if let Some(parent) = parent {
+            let tree = parent
+                .tree()
+                .map_err(operation("read the current commit", workdir))?;
+            index
+                .read_tree(&tree)
+                .map_err(operation("read the current commit", workdir))?;
+        }
+
+        for path in paths {
+            let full_path = workdir.join(path);
+            let contents = fs::read(&full_path)
+                .map_err(|error| IoError::new(FileOperation::Read, full_path.clone(), error))?;
+            let metadata = fs::metadata(&full_path)
+                .map_err(|error| IoError::new(FileOperation::Read, full_path.clone(), error))?;
+
+            let id: Oid = repository
+                .blob(&contents)
+                .map_err(operation("store a file", workdir))?;
+
+            index
+                .add(&IndexEntry {
+                    ctime: IndexTime::new(0, 0),
+                    mtime: IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode: file_mode(&metadata),
+                    uid: 0,
+                    gid: 0,
+                    file_size: contents.len() as u32,
+                    id,
+                    flags: 0,
+                    flags_extended: 0,
+                    path: path.to_string_lossy().replace('\\', "/").into_bytes(),
+                })
+                .map_err(operation("stage the generated files", workdir))?;
+        }
+
+        let tree_id = index
+            .write_tree_to(repository)
+            .map_err(operation("write the commit tree", workdir))?;
+
+        // keep the repository's own index in line with the commit
+        let mut staged = repository
+            .index()
+            .map_err(operation("stage the generated files", workdir))?;
+        for path in paths {
+            staged
+                .add_path(path)
+                .map_err(operation("stage the generated files", workdir))?;
+        }
+        staged
+            .write()
+            .map_err(operation("stage the generated files", workdir))?;
+
+        repository
+            .find_tree(tree_id)
+            .map_err(operation("write the commit tree", workdir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configure_identity(repository: &Repository) {
+        let mut config = repository.config().unwrap();
+        config.set_str("user.name", "Kopye").unwrap();
+        config.set_str("user.email", "kopye@example.com").unwrap();
+    }
+
+    #[test]
+    fn test_commit_in_existing_repository_uses_a_branch_and_skips_ignored_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let repository = Repository::init(directory.path()).unwrap();
+        configure_identity(&repository);
+
+        fs::write(directory.path().join(".gitignore"), ".env\n").unwrap();
+        let git = RenderedGit {
+            message: String::from("Initial commit"),
+            remote: None,
+            branch: String::from("main"),
+            enclosing: None,
+        };
+        git.commit(directory.path(), &[PathBuf::from(".gitignore")], false)
+            .unwrap();
+
+        let project = directory.path().join("app");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(project.join(".env"), "TOKEN=secret\n").unwrap();
+        fs::write(directory.path().join("notes.txt"), "unrelated\n").unwrap();
+
+        let git = RenderedGit {
+            message: String::from("Add app"),
+            remote: None,
+            branch: String::from("kopye/app"),
+            enclosing: Some(true),
+        };
+        git.commit(
+            &project,
+            &[PathBuf::from("main.rs"), PathBuf::from(".env")],
+            false,
+        )
+        .unwrap();
+
+        let head = repository.head().unwrap();
+        assert_eq!(head.shorthand(), Some("kopye/app"));
+
+        let commit = head.peel_to_commit().unwrap();
+        let tree = commit.tree().unwrap();
+        assert_eq!(commit.message(), Some("Add app"));
+        assert_eq!(commit.parent_count(), 1);
+        assert!(tree.get_path(Path::new("app/main.rs")).is_ok());
+        assert!(tree.get_path(Path::new(".gitignore")).is_ok());
+        assert!(tree.get_path(Path::new("app/.env")).is_err());
+        assert!(tree.get_path(Path::new("notes.txt")).is_err());
+    }
+
+    #[test]
+    fn test_commit_leaves_enclosing_repositories_alone_unless_allowed() {
+        let directory = tempfile::tempdir().unwrap();
+        let dotfiles = Repository::init(directory.path()).unwrap();
+        let project = directory.path().join("app");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("main.rs"), "fn main() {}\n").unwrap();
+        let project = project.canonicalize().unwrap();
+
+        let mut git = RenderedGit {
+            message: String::from("Initial commit"),
+            remote: None,
+            branch: String::from("kopye/app"),
+            enclosing: None,
+        };
+        let enclosing = Repository::discover(&project).unwrap();
+        assert!(!git.reuses(&enclosing, &project, false).unwrap());
+        git.enclosing = Some(true);
+        assert!(git.reuses(&enclosing, &project, false).unwrap());
+
+        // a repository of its own without commits gets the branch too
+        git.enclosing = None;
+        let repository = Repository::init(&project).unwrap();
+        configure_identity(&repository);
+        git.commit(&project, &[PathBuf::from("main.rs")], false)
+            .unwrap();
+
+        let head = repository.head().unwrap();
+        assert_eq!(head.shorthand(), Some("kopye/app"));
+        assert_eq!(head.peel_to_commit().unwrap().parent_count(), 0);
+        assert!(
+            dotfiles.head().is_err(),
+            "the enclosing repository is untouched"
+        );
+    }
+}
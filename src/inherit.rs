@@ -0,0 +1,227 @@
+use crate::{
+    prompt::{PromptError, QuestionsFile},
+    source::Source,
+    utils::normalize_path,
+};
+use miette::Diagnostic;
+use std::path::PathBuf;
+use tampopo::Graph;
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum InheritError {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Prompt(#[from] PromptError),
+
+    #[error("Blueprint `{blueprint}` extends `{parent}`, which is not in the source")]
+    #[diagnostic(
+        code(kopye::inherit::unknown_parent),
+        help("Add `{parent}` to blueprints.toml, or fix `extends` of `{blueprint}`")
+    )]
+    UnknownParent { blueprint: String, parent: String },
+
+    #[error("Blueprints extend one another in a cycle: {details}")]
+    #[diagnostic(
+        code(kopye::inherit::cycle),
+        help("Remove `extends` from one of the blueprints of the cycle")
+    )]
+    Cycle { details: String },
+}
+
+/// A blueprint of a [`Lineage`], with its own, unmerged, `blueprint.toml`.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub directory: PathBuf,
+    pub questions: QuestionsFile,
+}
+impl Layer {
+    pub fn blueprint_toml(&self) -> PathBuf {
+        self.directory.join("blueprint.toml")
+    }
+}
+
+/// A blueprint along with every blueprint it extends, e.g. `rust-cli` extending `rust-base`.
+#[derive(Debug, Clone)]
+pub struct Lineage {
+    /// The furthest ancestor first, the blueprint itself last
+    pub layers: Vec<Layer>,
+}
+impl Lineage {
+    /// The blueprint itself.
+    pub fn blueprint(&self) -> &Layer {
+        self.layers
+            .last()
+            .expect("a lineage has at least one layer")
+    }
+
+    /// The `blueprint.toml` of every layer merged into one, children overriding their parents.
+    pub fn questions(&self) -> Result<QuestionsFile, PromptError> {
+        let mut layers = self.layers.iter().map(|layer| layer.questions.clone());
+        let first = layers.next().expect("a lineage has at least one layer");
+
+        let merged = layers.fold(first, QuestionsFile::merge);
+
+        merged.validate()?;
+
+        Ok(merged)
+    }
+}
+
+/// Follows the `extends` of `blueprint`, which the source must define, up to the blueprint that
+/// extends none. The `extends` of `blueprint.toml` wins over the one of `blueprints.toml`.
+pub fn resolve(source: &Source, blueprint: &str) -> Result<Lineage, InheritError> {
+    let mut layers: Vec<Layer> = Vec::new();
+    let mut graph = Graph {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+    let mut name = blueprint.to_string();
+
+    loop {
+        let info = &source.projects[&name];
+        let directory = source.source_dir.join(normalize_path(&info.path));
+        let questions = QuestionsFile::from_file(directory.join("blueprint.toml"))?;
+
+        let parent = questions.extends.clone().or_else(|| info.extends.clone());
+
+        graph.nodes.push(name.clone());
+        layers.push(Layer {
+            name: name.clone(),
+            directory,
+            questions,
+        });
+
+        let Some(parent) = parent else {
+            break;
+        };
+
+        if !source.projects.contains_key(&parent) {
+            return Err(InheritError::UnknownParent {
+                blueprint: name,
+                parent,
+            });
+        }
+
+        graph.edges.push((parent.clone(), name));
+
+        // the edge closing a cycle is in the graph, which is all the sort needs to report it
+        if graph.nodes.contains(&parent) {
+            break;
+        }
+
+        name = parent;
+    }
+
+    if let Err(error) = tampopo::sort_graph(&graph) {
+        return Err(InheritError::Cycle {
+            details: error.to_string().trim_end().to_string(),
+        });
+    }
+
+    layers.reverse();
+
+    Ok(Lineage { layers })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::BlueprintInfo;
+    use indexmap::IndexMap;
+    use std::fs;
+
+    fn source_with(blueprints: &[(&str, &str)]) -> (tempfile::TempDir, Source) {
+        let directory = tempfile::tempdir().unwrap();
+        let mut projects = IndexMap::new();
+
+        for (name, blueprint_toml) in blueprints {
+            fs::create_dir(directory.path().join(name)).unwrap();
+            fs::write(
+                directory.path().join(name).join("blueprint.toml"),
+                blueprint_toml,
+            )
+            .unwrap();
+            projects.insert(
+                name.to_string(),
+                BlueprintInfo {
+                    path: name.to_string(),
+                    extends: None,
+                    addon: false,
+                },
+            );
+        }
+
+        let source = Source {
+            projects,
+            source_dir: directory.path().to_path_buf(),
+            shared: PathBuf::from("_shared"),
+            url: directory.path().display().to_string(),
+            commit: None,
+        };
+
+        (directory, source)
+    }
+
+    #[test]
+    fn test_resolve_merges_questions_and_detects_cycles() {
+        let (_directory, source) = source_with(&[
+            (
+                "rust-base",
+                r#"
+                version = 1
+
+                [groups.ci]
+                title = "CI"
+
+                [questions.name]
+                type = "string"
+                help = "Name?"
+
+                [questions.ci]
+                type = "bool"
+                help = "CI?"
+                group = "ci"
+                "#,
+            ),
+            (
+                "rust-cli",
+                r#"
+                version = 1
+                extends = "rust-base"
+
+                [questions.name]
+                type = "string"
+                help = "Binary name?"
+
+                [questions.clap]
+                type = "bool"
+                help = "Clap?"
+                group = "ci"
+                "#,
+            ),
+            ("first", "version = 1\nextends = \"second\"\n"),
+            ("second", "version = 1\nextends = \"first\"\n"),
+            ("orphan", "version = 1\nextends = \"missing\"\n"),
+        ]);
+
+        let lineage = resolve(&source, "rust-cli").unwrap();
+        let names = lineage.layers.iter().map(|layer| layer.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), ["rust-base", "rust-cli"]);
+
+        let questions = lineage.questions().unwrap();
+        let keys = questions.questions.keys().map(String::as_str);
+        assert_eq!(keys.collect::<Vec<_>>(), ["name", "ci", "clap"]);
+        assert_eq!(questions.questions["name"].help, "Binary name?");
+
+        assert!(matches!(
+            resolve(&source, "first"),
+            Err(InheritError::Cycle { .. })
+        ));
+        assert!(matches!(
+            resolve(&source, "orphan"),
+            Err(InheritError::UnknownParent { .. })
+        ));
+    }
+}
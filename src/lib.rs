@@ -1,9 +1,22 @@
+mod analysis;
+mod answers;
 pub mod api;
+mod autocomplete;
 mod errors;
+mod extract;
+mod filters;
+mod git;
+mod inherit;
+mod lint;
 mod preview;
 mod prompt;
+mod rules;
+mod scaffold;
+mod snapshot;
 mod source;
+mod tasks;
 mod template;
 mod transactions;
+mod trust;
 mod utils;
 mod vfs;
@@ -0,0 +1,442 @@
+use crate::{
+    inherit,
+    prompt::{Choices, Question, QuestionType, QuestionsFile},
+    rules::FileRules,
+    source::Source,
+    template::{analyze_blueprint, analyze_questions, blueprint_rules, KOPYE_NAMESPACE},
+    utils::{error_chain, normalize_path},
+};
+use colored::Colorize;
+use miette::Diagnostic;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+use tampopo::Graph;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum LintError {
+    #[error("Lint found {errors} error(s) and {warnings} warning(s)")]
+    #[diagnostic(
+        code(kopye::lint::failed),
+        help("Fix the errors above, then run `kopye lint` again")
+    )]
+    Failed { errors: usize, warnings: usize },
+
+    #[error("Unable to serialize the lint report")]
+    #[diagnostic(code(kopye::lint::serialize))]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// How to print a [`LintReport`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LintFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single problem found in a blueprint source.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// The blueprint, as named in `blueprints.toml`
+    pub blueprint: String,
+    pub severity: Severity,
+    /// A stable identifier for the kind of problem, e.g. `undefined-variable`
+    pub code: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LintReport {
+    pub findings: Vec<Finding>,
+}
+impl LintReport {
+    fn push(&mut self, blueprint: &str, severity: Severity, code: &'static str, message: String) {
+        self.findings.push(Finding {
+            blueprint: blueprint.to_string(),
+            severity,
+            code,
+            message,
+        });
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+
+    /// Prints the report to stdout, then fails if any finding is an error.
+    pub fn finish(&self, format: LintFormat) -> Result<(), LintError> {
+        match format {
+            LintFormat::Human => self.print_human(),
+            LintFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+        }
+
+        let errors = self.count(Severity::Error);
+
+        if errors > 0 {
+            return Err(LintError::Failed {
+                errors,
+                warnings: self.count(Severity::Warning),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn print_human(&self) {
+        let mut current: Option<&str> = None;
+
+        for finding in &self.findings {
+            if current != Some(finding.blueprint.as_str()) {
+                println!("{}", finding.blueprint.bold().bright_blue());
+                current = Some(&finding.blueprint);
+            }
+
+            let severity = match finding.severity {
+                Severity::Error => "error".red().bold(),
+                Severity::Warning => "warning".yellow().bold(),
+            };
+
+            println!(
+                "  {}[{}]: {}",
+                severity,
+                finding.code,
+                finding.message.replace('\n', "\n    ")
+            );
+        }
+
+        if self.findings.is_empty() {
+            println!("{}", "No problems found".green());
+        }
+    }
+}
+
+/// Checks every blueprint listed in the source's `blueprints.toml`.
+pub fn lint_source(source: &Source) -> LintReport {
+    let mut report = LintReport::default();
+
+    for (name, info) in &source.projects {
+        let blueprint_directory = source.source_dir.join(normalize_path(&info.path));
+
+        if !blueprint_directory.is_dir() {
+            report.push(
+                name,
+                Severity::Error,
+                "missing-path",
+                format!("'{}' is not a directory", blueprint_directory.display()),
+            );
+            continue;
+        }
+
+        let lineage = match inherit::resolve(source, name) {
+            Ok(lineage) => lineage,
+            Err(error) => {
+                report.push(
+                    name,
+                    Severity::Error,
+                    "invalid-blueprint",
+                    error_chain(&error),
+                );
+                continue;
+            }
+        };
+
+        let blueprint_toml = lineage.blueprint().blueprint_toml();
+
+        let questions = match lineage.questions() {
+            Ok(questions) => questions,
+            Err(error) => {
+                report.push(
+                    name,
+                    Severity::Error,
+                    "invalid-blueprint",
+                    error_chain(&error),
+                );
+                continue;
+            }
+        };
+
+        lint_dependencies(name, &questions, &mut report);
+        lint_choices(name, &questions, &mut report);
+
+        let mut usages = BTreeMap::new();
+        let mut is_valid = true;
+
+        // templates of the extended blueprints may use any question of the merged file
+        for layer in &lineage.layers {
+            let rules = match blueprint_rules(source, &layer.directory, &layer.questions) {
+                Ok(rules) => rules,
+                Err(error) => {
+                    report.push(
+                        name,
+                        Severity::Error,
+                        "invalid-pattern",
+                        error_chain(&error),
+                    );
+                    is_valid = false;
+                    break;
+                }
+            };
+
+            // the extended blueprints report their own files
+            if layer.name == name.as_str() {
+                lint_files(name, &layer.directory, &rules, &mut report);
+            }
+
+            match analyze_blueprint(&layer.directory, &rules) {
+                Ok(layer_usages) => {
+                    for (variable, used) in layer_usages {
+                        usages.entry(variable).or_insert_with(Vec::new).extend(used);
+                    }
+                }
+                Err(error) => {
+                    report.push(
+                        name,
+                        Severity::Error,
+                        "invalid-template",
+                        error_chain(&error),
+                    );
+                    is_valid = false;
+                    break;
+                }
+            }
+        }
+
+        if !is_valid {
+            continue;
+        }
+
+        if let Err(error) = analyze_questions(&questions, &blueprint_toml, &mut usages) {
+            report.push(
+                name,
+                Severity::Error,
+                "invalid-template",
+                error_chain(&error),
+            );
+            continue;
+        }
+
+        let defined = questions.variables();
+
+        for (variable, usages) in &usages {
+            if variable != KOPYE_NAMESPACE && !defined.contains(variable) {
+                let templates = usages
+                    .iter()
+                    .map(|usage| usage.template.as_str())
+                    .collect::<BTreeSet<_>>();
+
+                report.push(
+                    name,
+                    Severity::Error,
+                    "undefined-variable",
+                    format!(
+                        "`{}` is not defined by any question (used in: {})",
+                        variable,
+                        templates.into_iter().collect::<Vec<_>>().join(", ")
+                    ),
+                );
+            }
+        }
+
+        let dependencies = QuestionsFile::adjacency_list_from_file(questions.clone())
+            .into_iter()
+            .map(|(dependency, _)| dependency)
+            .collect::<BTreeSet<_>>();
+
+        for question in questions.questions.keys() {
+            let contents = format!("{}{}", question, Question::CONTENTS_SUFFIX);
+            let is_used = usages.contains_key(question)
+                || usages.contains_key(&contents)
+                || dependencies.contains(question);
+
+            if !is_used {
+                report.push(
+                    name,
+                    Severity::Warning,
+                    "unused-question",
+                    format!("`{}` is never used by a template or question", question),
+                );
+            }
+        }
+    }
+
+    report
+}
+
+/// Reports dependency cycles between questions.
+fn lint_dependencies(name: &str, questions: &QuestionsFile, report: &mut LintReport) {
+    let graph = Graph {
+        nodes: questions.questions.keys().cloned().collect(),
+        edges: QuestionsFile::adjacency_list_from_file(questions.clone()),
+    };
+
+    if let Err(error) = tampopo::sort_graph(&graph) {
+        report.push(
+            name,
+            Severity::Error,
+            "dependency-cycle",
+            error.to_string().trim_end().to_string(),
+        );
+    }
+
+    for (dependency, question) in &graph.edges {
+        if !questions.questions.contains_key(dependency) {
+            report.push(
+                name,
+                Severity::Error,
+                "unknown-dependency",
+                format!(
+                    "`{}` depends on `{}`, which is not a question",
+                    question, dependency
+                ),
+            );
+        }
+    }
+}
+
+/// Reports `[files]` globs matching no file or directory of the blueprint.
+fn lint_files(name: &str, blueprint_directory: &Path, rules: &FileRules, report: &mut LintReport) {
+    let paths = WalkDir::new(blueprint_directory)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(blueprint_directory).ok()?;
+
+            Some(relative.to_path_buf())
+        })
+        .collect::<Vec<_>>();
+
+    for pattern in rules.unmatched_files(&paths) {
+        report.push(
+            name,
+            Severity::Error,
+            "missing-file",
+            format!(
+                "[files] entry `{}` matches no file of the blueprint",
+                pattern
+            ),
+        );
+    }
+}
+
+/// Reports selection questions without choices, and choices sharing a value or label.
+fn lint_choices(name: &str, questions: &QuestionsFile, report: &mut LintReport) {
+    for (question, config) in &questions.questions {
+        if !matches!(
+            config.r#type,
+            QuestionType::Select | QuestionType::MultiSelect
+        ) {
+            continue;
+        }
+
+        let choices = match (&config.choices, &config.choices_from) {
+            (None, None) => {
+                report.push(
+                    name,
+                    Severity::Error,
+                    "missing-choices",
+                    format!("`{}` has neither `choices` nor `choices_from`", question),
+                );
+                continue;
+            }
+            (Some(Choices::Static(choices)), _) => choices,
+            // dynamic choices are only known when prompting
+            _ => continue,
+        };
+
+        if choices.is_empty() {
+            report.push(
+                name,
+                Severity::Error,
+                "missing-choices",
+                format!("`{}` has an empty list of choices", question),
+            );
+        }
+
+        let mut values: HashMap<&str, usize> = HashMap::new();
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+
+        for choice in choices {
+            *values.entry(choice.value()).or_default() += 1;
+            *labels.entry(choice.label()).or_default() += 1;
+        }
+
+        let duplicates = |counts: HashMap<&str, usize>| {
+            counts
+                .into_iter()
+                .filter(|(_, count)| *count > 1)
+                .map(|(key, _)| key.to_string())
+                .collect::<BTreeSet<_>>()
+        };
+
+        for value in duplicates(values) {
+            report.push(
+                name,
+                Severity::Error,
+                "duplicate-choice",
+                format!(
+                    "`{}` lists the choice value `{}` more than once",
+                    question, value
+                ),
+            );
+        }
+
+        for label in duplicates(labels) {
+            report.push(
+                name,
+                Severity::Warning,
+                "duplicate-label",
+                format!(
+                    "`{}` shows the label `{}` for several choices",
+                    question, label
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_choices_reports_missing_and_colliding_choices() {
+        let questions = QuestionsFile::parse(
+            r#"
+            [license]
+            type = "Select"
+
+            [ci]
+            type = "Select"
+            choices = ["github", "gitlab", { value = "github", label = "GitHub" }]
+            "#,
+        )
+        .unwrap();
+
+        let mut report = LintReport::default();
+        lint_choices("sample", &questions, &mut report);
+
+        let codes = report
+            .findings
+            .iter()
+            .map(|finding| finding.code)
+            .collect::<Vec<_>>();
+
+        assert_eq!(codes, vec!["missing-choices", "duplicate-choice"]);
+    }
+}
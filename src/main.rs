@@ -3,7 +3,7 @@ use clap::{
     Command,
 };
 use env_logger::Builder;
-use kopye::api::KopyeError;
+use kopye::api::{KopyeError, LintFormat, RenderOptions, TestOptions, UndefinedBehavior};
 use log::LevelFilter;
 use miette::Result as MietteResult;
 use std::env;
@@ -21,6 +21,34 @@ fn main() -> MietteResult<()> {
                 .action(ArgAction::SetTrue)
                 .global(true),
         )
+        .arg(
+            Arg::new("undefined")
+                .long("undefined")
+                .help("How to treat template variables that no question defines [default: the blueprint's setting, else strict]")
+                .value_parser(["strict", "lenient"])
+                .global(true),
+        )
+        .arg(
+            Arg::new("skip-tasks")
+                .long("skip-tasks")
+                .help("Do not run the blueprint's tasks: pre_render and post_render tasks run before the generated files are committed, and can roll them back; post_commit tasks run after")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("skip-git")
+                .long("skip-git")
+                .help("Do not commit the generated files, even if the blueprint asks to")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("trust")
+                .long("trust")
+                .help("Run the blueprint's local commands without asking, e.g. for automation")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
         .subcommand(
             Command::new("copy")
                 .about("Copies a template from a repo reference to a destination")
@@ -29,7 +57,12 @@ fn main() -> MietteResult<()> {
                         .help("git repository reference where templates live")
                         .required(true),
                 )
-                .arg(Arg::new("template").help("template name").required(true))
+                .arg(
+                    Arg::new("template")
+                        .help("template name, followed by any add-ons to apply along with it")
+                        .num_args(1..)
+                        .required(true),
+                )
                 .arg(
                     Arg::new("destination")
                         .help("The destination directory where the project will be created")
@@ -41,6 +74,92 @@ fn main() -> MietteResult<()> {
                 .about("list templates")
                 .arg(Arg::new("repo").help("git repository reference where templates live")),
         )
+        .subcommand(
+            Command::new("init")
+                .about("Creates a blueprint source with an example blueprint")
+                .arg(
+                    Arg::new("directory")
+                        .help("Directory of the new source")
+                        .default_value("."),
+                ),
+        )
+        .subcommand(
+            Command::new("add-blueprint")
+                .about("Registers a new blueprint in blueprints.toml")
+                .arg(Arg::new("name").help("blueprint name").required(true))
+                .arg(
+                    Arg::new("path")
+                        .long("path")
+                        .help("Directory of the blueprint, relative to the source [default: <name>]"),
+                )
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("Directory of the blueprint source")
+                        .default_value("."),
+                ),
+        )
+        .subcommand(
+            Command::new("extract")
+                .about("Creates a blueprint from an existing project")
+                .arg(
+                    Arg::new("project")
+                        .help("Directory of the project to extract")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Directory of the new blueprint")
+                        .default_value("blueprint"),
+                )
+                .arg(
+                    Arg::new("var")
+                        .long("var")
+                        .help("A value to turn into a question, e.g. --var project=myapp")
+                        .action(ArgAction::Append),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Checks the blueprints of a source for mistakes")
+                .arg(
+                    Arg::new("repo")
+                        .help("git repository reference where templates live")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Output format of the report")
+                        .value_parser(["human", "json"])
+                        .default_value("human"),
+                ),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Renders blueprints with fixture answers and compares them to snapshots")
+                .arg(
+                    Arg::new("repo")
+                        .help("git repository reference where templates live")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("update")
+                        .long("update")
+                        .help("Overwrite the snapshots with the rendered output")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("command")
+                        .long("command")
+                        .help("Command to run inside each rendered project, e.g. \"cargo check\""),
+                ),
+        )
+        .subcommand(
+            Command::new("filters").about("Lists the filters and functions available to templates"),
+        )
         .get_matches();
 
     let is_verbose = matches.get_flag("verbose");
@@ -58,6 +177,40 @@ fn main() -> MietteResult<()> {
 
             Ok(())
         }
+        Some(("init", args)) => {
+            let directory = args
+                .get_one::<String>("directory")
+                .expect("directory defaulted");
+
+            kopye::api::init_source(directory).map_err(miette::Report::new)?;
+
+            Ok(())
+        }
+        Some(("add-blueprint", args)) => {
+            handle_add_blueprint(args).map_err(miette::Report::new)?;
+
+            Ok(())
+        }
+        Some(("extract", args)) => {
+            handle_extract(args).map_err(miette::Report::new)?;
+
+            Ok(())
+        }
+        Some(("lint", args)) => {
+            handle_lint(args).map_err(miette::Report::new)?;
+
+            Ok(())
+        }
+        Some(("test", args)) => {
+            handle_test(args).map_err(miette::Report::new)?;
+
+            Ok(())
+        }
+        Some(("filters", _)) => {
+            kopye::api::list_filters();
+
+            Ok(())
+        }
         _ => unreachable!(),
     }
 }
@@ -76,20 +229,80 @@ fn init_logger(verbose: bool) {
     builder.init();
 }
 
+fn render_options(args: &ArgMatches) -> RenderOptions {
+    let undefined = match args.get_one::<String>("undefined").map(String::as_str) {
+        Some("lenient") => Some(UndefinedBehavior::Lenient),
+        Some(_) => Some(UndefinedBehavior::Strict),
+        None => None,
+    };
+
+    RenderOptions {
+        undefined,
+        trust: args.get_flag("trust"),
+        skip_tasks: args.get_flag("skip-tasks"),
+        skip_git: args.get_flag("skip-git"),
+        ..Default::default()
+    }
+}
+
 fn handle_copy(args: &ArgMatches) -> Result<(), KopyeError> {
     let repo = args.get_one::<String>("repo").expect("repo required");
-    let template_name = args
-        .get_one::<String>("template")
-        .expect("template required");
+    let templates = args
+        .get_many::<String>("template")
+        .expect("template required")
+        .cloned()
+        .collect::<Vec<_>>();
     let destination = args
         .get_one::<String>("destination")
         .expect("destination expected");
 
-    kopye::api::copy_template(repo, template_name, destination)
+    kopye::api::copy_template(repo, &templates, destination, &render_options(args))
 }
 
 fn handle_list(args: &ArgMatches) -> Result<(), KopyeError> {
     let repo = args.get_one::<String>("repo").expect("repo required");
 
-    kopye::api::list_templates(repo)
+    kopye::api::list_templates(repo, &render_options(args))
+}
+
+fn handle_lint(args: &ArgMatches) -> Result<(), KopyeError> {
+    let repo = args.get_one::<String>("repo").expect("repo required");
+    let format = match args.get_one::<String>("format").map(String::as_str) {
+        Some("json") => LintFormat::Json,
+        _ => LintFormat::Human,
+    };
+
+    kopye::api::lint_source(repo, format)
+}
+
+fn handle_test(args: &ArgMatches) -> Result<(), KopyeError> {
+    let repo = args.get_one::<String>("repo").expect("repo required");
+
+    let options = TestOptions {
+        update: args.get_flag("update"),
+        command: args.get_one::<String>("command").cloned(),
+        render: render_options(args),
+    };
+
+    kopye::api::test_blueprints(repo, &options)
+}
+
+fn handle_add_blueprint(args: &ArgMatches) -> Result<(), KopyeError> {
+    let name = args.get_one::<String>("name").expect("name required");
+    let source = args.get_one::<String>("source").expect("source defaulted");
+    let path = args.get_one::<String>("path").map(String::as_str);
+
+    kopye::api::add_blueprint(source, name, path)
+}
+
+fn handle_extract(args: &ArgMatches) -> Result<(), KopyeError> {
+    let project = args.get_one::<String>("project").expect("project required");
+    let output = args.get_one::<String>("output").expect("output defaulted");
+    let variables = args
+        .get_many::<String>("var")
+        .unwrap_or_default()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    kopye::api::extract_blueprint(project, output, &variables)
 }
@@ -1,14 +1,23 @@
 use crate::{
+    analysis::referenced_variables,
+    autocomplete::PathCompleter,
     errors::{FileFormat, FileOperation, IoError, ParseError},
+    git::GitSettings,
+    rules::FileSettings,
     source::Source,
+    tasks::{Task, TaskCommand},
+    template::UndefinedBehavior,
+    utils::normalize_path,
 };
+use colored::Colorize;
 use indexmap::IndexMap;
 use inquire::{
-    required, validator::MinLengthValidator, Confirm, Editor, InquireError, MultiSelect, Select,
-    Text,
+    required,
+    validator::{MinLengthValidator, Validation},
+    Confirm, Editor, InquireError, MultiSelect, Password, PasswordDisplayMode, Select, Text,
 };
 use miette::Diagnostic;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use std::{
     collections::{HashMap, HashSet},
     fs,
@@ -34,6 +43,81 @@ pub enum PromptError {
         source: InquireError,
     },
 
+    #[error("Computed question `{question}` has no `value` to evaluate")]
+    #[diagnostic(
+        code(kopye::prompt::missing_computed_value),
+        help("Add a tera expression, e.g. value = \"{{{{ project | crate_name }}}}\"")
+    )]
+    MissingComputedValue { question: String },
+
+    #[error("Question `{question}` runs a local command to list its choices: {command}")]
+    #[diagnostic(
+        code(kopye::prompt::untrusted_command),
+        help("Review the command in blueprint.toml and pass `--trust` to allow it")
+    )]
+    UntrustedCommand { question: String, command: String },
+
+    #[error("Unable to run `{command}` for the choices of question `{question}`")]
+    #[diagnostic(code(kopye::prompt::choices_command))]
+    ChoicesCommand {
+        question: String,
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("`{command}` failed for the choices of question `{question}` ({status}): {stderr}")]
+    #[diagnostic(code(kopye::prompt::choices_command_failed))]
+    ChoicesCommandFailed {
+        question: String,
+        command: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+
+    #[error("Question `{question}` has no choices to select from")]
+    #[diagnostic(
+        code(kopye::prompt::no_choices),
+        help("Check the `choices` or `choices_from` of the question")
+    )]
+    NoChoices { question: String },
+
+    #[error("The choices of question `{question}` do not evaluate to a list: {rendered}")]
+    #[diagnostic(
+        code(kopye::prompt::invalid_choices),
+        help("`choices` must be a list, or a tera expression evaluating to one, e.g. \"targets | map(attribute='name')\"")
+    )]
+    InvalidChoices { question: String, rendered: String },
+
+    #[error("Question `{question}` has no answer and prompting is disabled")]
+    #[diagnostic(
+        code(kopye::prompt::missing_answer),
+        help("Add `{question}` to the answers")
+    )]
+    MissingAnswer { question: String },
+
+    #[error("Invalid answer for question `{question}`: expected {expected}, got {value}")]
+    #[diagnostic(code(kopye::prompt::invalid_answer))]
+    InvalidAnswer {
+        question: String,
+        expected: String,
+        value: String,
+    },
+
+    #[error("Unsupported blueprint file version {version} in '{path}'")]
+    #[diagnostic(
+        code(kopye::prompt::unsupported_version),
+        help("Upgrade kopye, or lower the `version` of the blueprint file")
+    )]
+    UnsupportedVersion { path: PathBuf, version: i64 },
+
+    #[error("Question `{question}` belongs to undefined group `{group}`")]
+    #[diagnostic(
+        code(kopye::prompt::unknown_group),
+        help("Define the group in blueprint.toml, e.g. [groups.{group}]")
+    )]
+    UnknownGroup { question: String, group: String },
+
     #[error("DAG sort error within prompt domain: {details}")]
     #[diagnostic(code(kopye::prompt::sort))]
     Sort {
@@ -71,18 +155,140 @@ pub enum Dependency {
     Or { any: Vec<String> },
 }
 /// The type of prompt to display.
+///
+/// Besides the variant names, lowercase aliases such as `"string"` or `"bool"` are accepted.
 #[derive(Debug, Deserialize, Clone)]
 pub enum QuestionType {
     /// A single-line text input
+    #[serde(alias = "text", alias = "string")]
     Text,
     /// A multi-line text input
+    #[serde(alias = "paragraph")]
     Paragraph,
     /// A confirmation (yes/no) prompt
+    #[serde(alias = "confirm", alias = "bool", alias = "boolean")]
     Confirm,
     /// A single-select prompt
+    #[serde(alias = "select")]
     Select,
     /// A multi-select prompt
+    #[serde(alias = "multiselect", alias = "multi_select")]
     MultiSelect,
+    /// A value derived from other answers, which is never prompted
+    #[serde(alias = "computed")]
+    Computed,
+    /// A masked input for secrets such as tokens
+    #[serde(alias = "password")]
+    Password,
+    /// A filesystem path, with autocompletion
+    #[serde(alias = "path")]
+    Path,
+}
+
+/// The kind of filesystem entry a `Path` question accepts.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PathKind {
+    File,
+    Dir,
+}
+
+/// The directory a relative `Path` answer is resolved against.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PathBase {
+    /// The directory kopye was invoked from
+    #[default]
+    Cwd,
+    /// The directory the blueprint is rendered into
+    Destination,
+}
+
+/// A choice offered by a select prompt.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Choice {
+    /// A plain choice, shown to the user and passed to templates as-is
+    Value(String),
+    /// A choice whose `label` (and optional `help`) is shown, but whose `value` is stored
+    Detailed {
+        value: String,
+        label: Option<String>,
+        help: Option<String>,
+    },
+}
+impl Choice {
+    /// The value stored in [`Answer`] when this choice is selected.
+    pub fn value(&self) -> &str {
+        match self {
+            Choice::Value(value) => value,
+            Choice::Detailed { value, .. } => value,
+        }
+    }
+
+    /// The text shown to the user for this choice.
+    pub fn label(&self) -> &str {
+        match self {
+            Choice::Value(value) => value,
+            Choice::Detailed { value, label, .. } => label.as_deref().unwrap_or(value),
+        }
+    }
+}
+impl std::fmt::Display for Choice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Choice::Detailed {
+                help: Some(help), ..
+            } => write!(f, "{} - {}", self.label(), help),
+            _ => write!(f, "{}", self.label()),
+        }
+    }
+}
+
+/// The choices offered by a select prompt.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Choices {
+    /// A fixed list of choices
+    Static(Vec<Choice>),
+    /// A tera expression evaluating to a list, e.g. `"targets | filter(attribute='os', value=os)"`,
+    /// or a template rendering a JSON array or one choice per line
+    Dynamic(String),
+}
+impl Choices {
+    /// Wraps a `Dynamic` expression into a template that renders it as JSON.
+    ///
+    /// Values that already contain tera delimiters are used as templates unchanged.
+    pub fn template(expression: &str) -> String {
+        json_template("__choices", expression)
+    }
+}
+
+/// Whether `value` is a tera template rather than a bare expression.
+fn is_template(value: &str) -> bool {
+    value.contains("{{") || value.contains("{%")
+}
+
+/// Wraps `expression` into a template rendering it as JSON through the variable `name`, unless it
+/// already is a template.
+fn json_template(name: &str, expression: &str) -> String {
+    if is_template(expression) {
+        expression.to_string()
+    } else {
+        format!(
+            "{{% set {name} = {expression} %}}{{{{ {name} | json_encode() }}}}",
+            name = name,
+            expression = expression
+        )
+    }
+}
+
+/// A local command whose output provides the choices for a select prompt.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChoicesFrom {
+    /// The program and its arguments, each rendered as a tera template.
+    /// Every non-empty line of its output becomes a choice.
+    pub command: Vec<String>,
 }
 
 /// Configuration for a single prompt question.
@@ -91,43 +297,363 @@ pub struct Question {
     /// The type of the question (e.g., text, paragraph, confirm)
     pub r#type: QuestionType,
     /// Help text describing the prompt.
+    #[serde(default)]
     pub help: String,
     /// Optional list of choices for selection prompts
-    pub choices: Option<Vec<String>>,
+    pub choices: Option<Choices>,
+    /// Optional command providing the choices for selection prompts; requires trust
+    pub choices_from: Option<ChoicesFrom>,
+    /// Whether a question with choices accepts several of them
+    #[serde(default)]
+    pub multiselect: bool,
+    /// Tera expression evaluated to the answer of a `Computed` question, e.g.
+    /// `"project | kebab_case"`, keeping its type. Values containing tera delimiters are
+    /// rendered as templates, to a string.
+    pub value: Option<String>,
+    /// Whether a `Password` must be entered twice
+    #[serde(default)]
+    pub confirm: bool,
+    /// Environment variable a `Password` is read from instead of prompting,
+    /// defaults to `KOPYE_<QUESTION>`
+    pub env: Option<String>,
+    /// Whether a `Path` must already exist
+    #[serde(default)]
+    pub must_exist: bool,
+    /// The kind of entry a `Path` must point to, when it exists
+    pub kind: Option<PathKind>,
+    /// The directory a relative `Path` is resolved against
+    #[serde(default)]
+    pub relative_to: PathBase,
+    /// Whether to read the file a `Path` points to into `<question>_contents`
+    #[serde(default)]
+    pub read: bool,
+    /// Optional group, from the `groups` table, the question is shown under
+    pub group: Option<String>,
     /// Optional dependency that determines whether the prompt should be displayed
     #[serde(rename = "depends_on")]
     pub raw_dependency: Option<Dependency>,
 }
 
-/// Represents a collection of questions loaded from a file.
+impl Question {
+    /// Resolves the `string` + `choices` (+ `multiselect = true`) shorthand into a `Select` or
+    /// `MultiSelect` question.
+    fn normalize(&mut self) {
+        let has_choices = self.choices.is_some() || self.choices_from.is_some();
+
+        self.r#type = match self.r#type {
+            QuestionType::Text | QuestionType::Select if has_choices && self.multiselect => {
+                QuestionType::MultiSelect
+            }
+            QuestionType::Text if has_choices => QuestionType::Select,
+            ref other => other.clone(),
+        };
+    }
+
+    /// Wraps a `Computed` value into a template that renders it as JSON, see [`Question::value`].
+    pub fn value_template(value: &str) -> String {
+        json_template("__value", value)
+    }
+
+    /// Suffix of the variable holding the contents of a `Path` question's file, see `read`.
+    pub const CONTENTS_SUFFIX: &str = "_contents";
+
+    /// Every tera template this question renders before it is asked: a `Computed` value,
+    /// `Dynamic` choices and the arguments of a `choices_from` command.
+    pub fn templates(&self) -> Vec<String> {
+        let value = match self.r#type {
+            QuestionType::Computed => self.value.as_deref().map(Question::value_template),
+            _ => None,
+        };
+
+        let choices = match &self.choices {
+            Some(Choices::Dynamic(expression)) => Some(Choices::template(expression)),
+            _ => None,
+        };
+
+        let command = self
+            .choices_from
+            .iter()
+            .flat_map(|choices_from| choices_from.command.iter().cloned());
+
+        value.into_iter().chain(choices).chain(command).collect()
+    }
+}
+
+/// A group of questions shown under a common heading, e.g. `[groups.ci]`.
 #[derive(Debug, Deserialize, Clone)]
-pub struct QuestionsFile(pub IndexMap<String, Question>);
+pub struct Group {
+    /// Heading shown before the group's first question
+    pub title: String,
+    /// Optional text shown under the heading
+    pub description: Option<String>,
+    /// Optional dependency that determines whether any question of the group is displayed
+    #[serde(rename = "depends_on")]
+    pub raw_dependency: Option<Dependency>,
+}
+
+/// Blueprint-wide settings, from the `[settings]` table of a versioned blueprint file.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Settings {
+    /// How to treat undefined template variables, unless overridden on the command line
+    pub undefined: Option<UndefinedBehavior>,
+    /// Files left out of the generated project, as `.gitignore` patterns
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Files copied without rendering their name or contents, as `.gitignore` patterns
+    #[serde(default)]
+    pub copy_only: Vec<String>,
+}
+
+/// The blueprint file layout before `version` was introduced: every top-level table is a
+/// question, even one named `groups`, `tasks` or `git`, which only the versioned layout reserves.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+struct LegacyQuestionsFile {
+    questions: IndexMap<String, Question>,
+}
+
+/// A versioned blueprint file, where questions live under a dedicated `[questions]` table.
+#[derive(Debug, Deserialize)]
+struct VersionedQuestionsFile {
+    version: i64,
+    #[serde(default)]
+    settings: Settings,
+    #[serde(default)]
+    groups: IndexMap<String, Group>,
+    #[serde(default)]
+    tasks: Vec<Task>,
+    #[serde(default)]
+    git: Option<GitSettings>,
+    #[serde(default)]
+    files: IndexMap<String, FileSettings>,
+    #[serde(default)]
+    extends: Option<String>,
+    #[serde(default)]
+    delete: Vec<String>,
+    #[serde(default)]
+    questions: IndexMap<String, Question>,
+}
+
+/// Reads only the top-level `version` key, to tell both blueprint file layouts apart.
+#[derive(Debug, Deserialize)]
+struct VersionProbe {
+    version: Option<toml::Value>,
+}
+
+/// Represents a collection of questions loaded from a file.
+#[derive(Debug, Clone)]
+pub struct QuestionsFile {
+    /// Schema version of the file, `0` for the legacy flat layout
+    pub version: i64,
+    pub settings: Settings,
+    /// Question groups, under the `groups` table
+    pub groups: IndexMap<String, Group>,
+    /// Commands run around generation, under `[[tasks]]`
+    pub tasks: Vec<Task>,
+    /// How the generated files are committed, under `[git]`
+    pub git: Option<GitSettings>,
+    /// Settings of individual files and directories, under `[files]`, keyed by their path
+    pub files: IndexMap<String, FileSettings>,
+    /// Name of the blueprint this one builds upon, under `extends`
+    pub extends: Option<String>,
+    /// Globs of files the extended blueprint generates that this one leaves out, under `delete`
+    pub delete: Vec<String>,
+    /// Questions, in file order
+    pub questions: IndexMap<String, Question>,
+}
 impl QuestionsFile {
-    /// Loads and parses a questions file from the given path.
+    /// The latest schema version kopye understands.
+    pub const CURRENT_VERSION: i64 = 1;
+
+    /// Loads and parses a questions file from the given path. It is not validated, as it may only
+    /// be complete once merged with the blueprint it extends, see [`QuestionsFile::validate`].
     pub fn from_file(path: PathBuf) -> Result<Self, PromptError> {
         let content = fs::read_to_string(path.clone())
             .map_err(|err| IoError::new(FileOperation::Read, path.clone(), err))?;
-        let parsed: QuestionsFile = toml::from_str(&content)
+        let parsed = QuestionsFile::parse(&content)
             .map_err(|err| ParseError::new(FileFormat::Toml, path.clone(), err))?;
 
+        if parsed.version > QuestionsFile::CURRENT_VERSION {
+            return Err(PromptError::UnsupportedVersion {
+                path,
+                version: parsed.version,
+            });
+        }
+
         Ok(parsed)
     }
 
+    /// Checks that every question belongs to a defined group.
+    pub fn validate(&self) -> Result<(), PromptError> {
+        for (question, config) in &self.questions {
+            if let Some(group) = &config.group {
+                if !self.groups.contains_key(group) {
+                    return Err(PromptError::UnknownGroup {
+                        question: question.clone(),
+                        group: group.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Overlays the file of a blueprint extending this one.
+    ///
+    /// Questions and groups of the `child` replace those of the same name, in place, and new ones
+    /// come after the inherited ones. Tasks run after the inherited ones, and `git` and
+    /// `undefined` are inherited unless the child sets them. The remaining settings, `[files]` and
+    /// `delete` only apply to the files of the blueprint declaring them, so they are the child's.
+    pub fn merge(mut self, child: QuestionsFile) -> QuestionsFile {
+        self.groups.extend(child.groups);
+        self.tasks.extend(child.tasks);
+        self.questions.extend(child.questions);
+
+        QuestionsFile {
+            version: child.version,
+            settings: Settings {
+                undefined: child.settings.undefined.or(self.settings.undefined),
+                ..child.settings
+            },
+            groups: self.groups,
+            tasks: self.tasks,
+            git: child.git.or(self.git),
+            files: child.files,
+            extends: child.extends,
+            delete: child.delete,
+            questions: self.questions,
+        }
+    }
+
+    /// Parses either blueprint file layout: a versioned file when it has an integer `version`
+    /// key, the legacy flat layout otherwise.
+    pub fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        let probe: VersionProbe = toml::from_str(content)?;
+
+        let mut parsed = match probe.version {
+            Some(toml::Value::Integer(_)) => {
+                let file: VersionedQuestionsFile = toml::from_str(content)?;
+
+                QuestionsFile {
+                    version: file.version,
+                    settings: file.settings,
+                    groups: file.groups,
+                    tasks: file.tasks,
+                    git: file.git,
+                    files: file.files,
+                    extends: file.extends,
+                    delete: file.delete,
+                    questions: file.questions,
+                }
+            }
+            // a legacy file may well have a question named `version`
+            _ => {
+                let file: LegacyQuestionsFile = toml::from_str(content)?;
+
+                QuestionsFile {
+                    version: 0,
+                    settings: Settings::default(),
+                    groups: IndexMap::new(),
+                    tasks: Vec::new(),
+                    git: None,
+                    files: IndexMap::new(),
+                    extends: None,
+                    delete: Vec::new(),
+                    questions: file.questions,
+                }
+            }
+        };
+
+        for question in parsed.questions.values_mut() {
+            question.normalize();
+        }
+
+        Ok(parsed)
+    }
+
+    /// The group a question belongs to, if any.
+    pub fn group_of(&self, question: &str) -> Option<(&String, &Group)> {
+        let name = self.questions.get(question)?.group.as_ref()?;
+
+        self.groups.get_key_value(name)
+    }
+
+    /// Whether a question's own `depends_on`, and that of its group, are satisfied by the answers
+    /// gathered so far.
+    pub fn is_enabled(&self, question: &str, answers: &IndexMap<String, Answer>) -> bool {
+        let group_dependency = self
+            .group_of(question)
+            .and_then(|(_, group)| group.raw_dependency.as_ref());
+        let dependency = self
+            .questions
+            .get(question)
+            .and_then(|config| config.raw_dependency.as_ref());
+
+        [group_dependency, dependency]
+            .into_iter()
+            .flatten()
+            .all(|dep| match dep {
+                Dependency::Condition(val) => check_dependency(val, answers),
+                Dependency::And { all } => all.iter().all(|d| check_dependency(d, answers)),
+                Dependency::Or { any } => any.iter().any(|d| check_dependency(d, answers)),
+            })
+    }
+
+    /// Every variable answering these questions may define, i.e. the question names and the
+    /// `<question>_contents` of `Path` questions that `read` their file.
+    pub fn variables(&self) -> Vec<String> {
+        self.questions
+            .iter()
+            .flat_map(|(name, question)| {
+                let contents = match (&question.r#type, question.read) {
+                    (QuestionType::Path, true) => {
+                        Some(format!("{}{}", name, Question::CONTENTS_SUFFIX))
+                    }
+                    _ => None,
+                };
+
+                std::iter::once(name.clone()).chain(contents)
+            })
+            .collect()
+    }
+
     /// Constructs an adjacency list representing dependencies between questions.
     /// Each dependency in a question is parsed into an edge from the dependency question to the current question.
+    ///
+    /// Questions also get an edge from every dependency of their group, and from every question
+    /// referenced by their [`Question::templates`].
     pub fn adjacency_list_from_file(file: QuestionsFile) -> Vec<(String, String)> {
-        file.0
+        let template_edges = file
+            .questions
             .iter()
             .flat_map(|(question_key, question_config)| {
-                let dependencies: Vec<&str> = match &question_config.raw_dependency {
-                    Some(Dependency::Condition(val)) => vec![val.as_str()],
-                    Some(Dependency::And { all }) => all.iter().map(String::as_str).collect(),
-                    Some(Dependency::Or { any }) => any.iter().map(String::as_str).collect(),
-                    None => Vec::new(),
-                };
+                question_config
+                    .templates()
+                    .into_iter()
+                    // parse errors are reported when the template is rendered
+                    .flat_map(|template| referenced_variables(&template).unwrap_or_default())
+                    .filter(|variable| file.questions.contains_key(variable))
+                    .map(|variable| (variable, question_key.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        file.questions
+            .iter()
+            .flat_map(|(question_key, question_config)| {
+                let group_dependency = file
+                    .group_of(question_key)
+                    .and_then(|(_, group)| group.raw_dependency.as_ref());
 
-                dependencies
+                [group_dependency, question_config.raw_dependency.as_ref()]
                     .into_iter()
+                    .flatten()
+                    .flat_map(|dependency| match dependency {
+                        Dependency::Condition(val) => vec![val.as_str()],
+                        Dependency::And { all } => all.iter().map(String::as_str).collect(),
+                        Dependency::Or { any } => any.iter().map(String::as_str).collect(),
+                    })
                     .filter_map(|dep_str| {
                         // Split dependency string "dependency_question:expected_answer"
                         dep_str.split_once(':').map(|(dependency_question, _)| {
@@ -136,10 +662,56 @@ impl QuestionsFile {
                     })
                     .collect::<Vec<_>>()
             })
+            .chain(template_edges)
             .collect()
     }
 }
 
+/// A sensitive answer, such as a token, which is masked whenever it is displayed,
+/// logged or serialized. Only templates see the actual value, through [`Secret::expose`].
+#[derive(PartialEq, Clone)]
+pub struct Secret(String);
+impl Secret {
+    const MASK: &str = "********";
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Secret::MASK)
+    }
+}
+impl std::fmt::Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Secret::MASK)
+    }
+}
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(Secret::MASK)
+    }
+}
+
+/// Settings that affect how questions are asked and answered.
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+    /// Allows `choices_from` commands to run
+    pub allow_commands: bool,
+    /// The directory the blueprint is rendered into
+    pub destination: PathBuf,
+    /// Answers given up front, e.g. from a test fixture; these questions are not prompted
+    pub answers: IndexMap<String, toml::Value>,
+    /// Fail on questions without an answer in [`PromptOptions::answers`] instead of prompting
+    pub non_interactive: bool,
+}
+
 /// Represents an answer to a prompt.
 #[derive(Debug, Serialize, PartialEq, Clone)]
 pub enum Answer {
@@ -148,12 +720,46 @@ pub enum Answer {
     // Float(f64),
     Bool(bool),
     Array(Vec<String>),
+    /// Never persisted, see [`Secret`]
+    Secret(Secret),
+    /// Any other value a `Computed` expression evaluates to, e.g. a number
+    Value(serde_json::Value),
+}
+impl From<serde_json::Value> for Answer {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(value) => Answer::String(value),
+            serde_json::Value::Bool(value) => Answer::Bool(value),
+            serde_json::Value::Array(values) if values.iter().all(|value| value.is_string()) => {
+                Answer::Array(
+                    values
+                        .into_iter()
+                        .filter_map(|value| value.as_str().map(str::to_string))
+                        .collect(),
+                )
+            }
+            other => Answer::Value(other),
+        }
+    }
+}
+impl std::fmt::Display for Answer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Answer::String(answer) => write!(f, "{}", answer),
+            Answer::Bool(answer) => write!(f, "{}", answer),
+            Answer::Array(answer) => write!(f, "{}", answer.join(", ")),
+            Answer::Secret(answer) => write!(f, "{}", answer),
+            Answer::Value(answer) => write!(f, "{}", answer),
+        }
+    }
 }
 
 /// Prompts the user with a question based on its configuration, and stores the answer.
 fn try_prompt(
     question: &str,
     config: &Question,
+    choices: Option<Vec<Choice>>,
+    options: &PromptOptions,
     answers: &mut IndexMap<String, Answer>,
 ) -> Result<(), PromptError> {
     match config.r#type {
@@ -199,7 +805,7 @@ fn try_prompt(
             answers.insert(question.to_string(), Answer::Bool(answer));
         }
         QuestionType::Select => {
-            if let Some(choices) = config.choices.clone() {
+            if let Some(choices) = choices {
                 let answer = Select::new(question, choices)
                     .with_help_message(&config.help)
                     .prompt()
@@ -208,11 +814,14 @@ fn try_prompt(
                         source: error,
                     })?;
 
-                answers.insert(question.to_string(), Answer::String(answer));
+                answers.insert(
+                    question.to_string(),
+                    Answer::String(answer.value().to_string()),
+                );
             }
         }
         QuestionType::MultiSelect => {
-            if let Some(choices) = config.choices.clone() {
+            if let Some(choices) = choices {
                 let answer = MultiSelect::new(question, choices)
                     .with_help_message(&config.help)
                     .with_validator(MinLengthValidator::new(1))
@@ -222,14 +831,213 @@ fn try_prompt(
                         source: error,
                     })?;
 
-                answers.insert(question.to_string(), Answer::Array(answer));
+                let values = answer
+                    .iter()
+                    .map(|choice| choice.value().to_string())
+                    .collect();
+
+                answers.insert(question.to_string(), Answer::Array(values));
             }
         }
+        // evaluated by `get_answers`, never prompted
+        QuestionType::Computed => {}
+        // read from the environment by `answer_question` when set
+        QuestionType::Password => {
+            let mut prompt = Password::new(question)
+                .with_help_message(&config.help)
+                .with_display_mode(PasswordDisplayMode::Masked);
+
+            if !config.confirm {
+                prompt = prompt.without_confirmation();
+            }
+
+            let answer = prompt.prompt().map_err(|error| PromptError::Prompt {
+                question: question.to_string(),
+                source: error,
+            })?;
+
+            answers.insert(question.to_string(), Answer::Secret(Secret(answer)));
+        }
+        QuestionType::Path => {
+            let base = path_base(config, options);
+            let resolve = {
+                let base = base.clone();
+                move |input: &str| resolve_path(&base, input)
+            };
+            let (must_exist, kind) = (config.must_exist, config.kind);
+
+            let answer = Text::new(question)
+                .with_help_message(&config.help)
+                .with_autocomplete(PathCompleter {
+                    base,
+                    directories_only: kind == Some(PathKind::Dir),
+                })
+                .with_validator(required!(format!("{} is required", question)))
+                .with_validator({
+                    let resolve = resolve.clone();
+                    move |input: &str| {
+                        let path = resolve(input);
+
+                        let validation = match path_mismatch(&path, must_exist, kind) {
+                            Some(_) if !path.exists() => Validation::Invalid(
+                                format!("{} does not exist", path.display()).into(),
+                            ),
+                            Some(expected) => Validation::Invalid(
+                                format!("{} is not {}", path.display(), expected).into(),
+                            ),
+                            None => Validation::Valid,
+                        };
+
+                        Ok(validation)
+                    }
+                })
+                .prompt()
+                .map_err(|error| PromptError::Prompt {
+                    question: question.to_string(),
+                    source: error,
+                })?;
+
+            store_path(question, config, resolve(&answer), answers)?;
+        }
     }
 
     Ok(())
 }
 
+/// The directory relative paths are resolved against for a `Path` question.
+fn path_base(config: &Question, options: &PromptOptions) -> PathBuf {
+    match config.relative_to {
+        PathBase::Cwd => PathBuf::from("."),
+        PathBase::Destination => options.destination.clone(),
+    }
+}
+
+/// What `path` should have been when it does not meet the `must_exist` and `kind` of a `Path`
+/// question, e.g. "a directory".
+fn path_mismatch(path: &Path, must_exist: bool, kind: Option<PathKind>) -> Option<&'static str> {
+    match (path.exists(), kind) {
+        (false, _) if must_exist => Some("an existing path"),
+        (true, Some(PathKind::File)) if !path.is_file() => Some("a file"),
+        (true, Some(PathKind::Dir)) if !path.is_dir() => Some("a directory"),
+        _ => None,
+    }
+}
+
+fn resolve_path(base: &Path, input: &str) -> PathBuf {
+    let joined = std::path::absolute(base.join(input)).unwrap_or_else(|_| base.join(input));
+
+    normalize_path(&joined.display().to_string())
+}
+
+/// Stores the answer to a `Path` question, along with the file contents when it `read`s them.
+fn store_path(
+    question: &str,
+    config: &Question,
+    path: PathBuf,
+    answers: &mut IndexMap<String, Answer>,
+) -> Result<(), PromptError> {
+    if config.read {
+        let contents = fs::read_to_string(&path)
+            .map_err(|error| IoError::new(FileOperation::Read, path.clone(), error))?;
+
+        answers.insert(
+            format!("{}{}", question, Question::CONTENTS_SUFFIX),
+            Answer::String(contents),
+        );
+    }
+
+    answers.insert(
+        question.to_string(),
+        Answer::String(path.display().to_string()),
+    );
+
+    Ok(())
+}
+
+/// Reads the answer of a `Password` question from its environment variable, see [`Question::env`],
+/// looking variables up with `lookup`.
+fn env_secret(
+    question: &str,
+    config: &Question,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<Secret> {
+    if !matches!(config.r#type, QuestionType::Password) {
+        return None;
+    }
+
+    let variable = config
+        .env
+        .clone()
+        .unwrap_or_else(|| format!("KOPYE_{}", question.to_uppercase()));
+
+    let answer = lookup(&variable)?;
+    log::debug!("reading `{}` from ${}", question, variable);
+
+    Some(Secret(answer))
+}
+
+/// Stores an answer given up front in [`PromptOptions::answers`], after checking it against the
+/// question's type and choices.
+fn apply_answer(
+    question: &str,
+    config: &Question,
+    value: &toml::Value,
+    choices: Option<&[Choice]>,
+    options: &PromptOptions,
+    answers: &mut IndexMap<String, Answer>,
+) -> Result<(), PromptError> {
+    let invalid = |expected: &str| PromptError::InvalidAnswer {
+        question: question.to_string(),
+        expected: expected.to_string(),
+        value: value.to_string(),
+    };
+    let is_choice = |candidate: &str| {
+        choices.is_none_or(|choices| choices.iter().any(|choice| choice.value() == candidate))
+    };
+
+    let answer = match (&config.r#type, value) {
+        (QuestionType::Confirm, toml::Value::Boolean(answer)) => Answer::Bool(*answer),
+        (QuestionType::Confirm, _) => return Err(invalid("a boolean")),
+        (QuestionType::Select, toml::Value::String(answer)) if is_choice(answer) => {
+            Answer::String(answer.clone())
+        }
+        (QuestionType::Select, _) => return Err(invalid("one of the choices")),
+        (QuestionType::MultiSelect, toml::Value::Array(values)) => {
+            let values = values
+                .iter()
+                .map(|value| match value {
+                    toml::Value::String(answer) if is_choice(answer) => Ok(answer.clone()),
+                    _ => Err(invalid("a list of the choices")),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Answer::Array(values)
+        }
+        (QuestionType::MultiSelect, _) => return Err(invalid("a list of the choices")),
+        (QuestionType::Password, toml::Value::String(answer)) => {
+            Answer::Secret(Secret(answer.clone()))
+        }
+        (QuestionType::Path, toml::Value::String(answer)) => {
+            let path = resolve_path(&path_base(config, options), answer);
+
+            if let Some(expected) = path_mismatch(&path, config.must_exist, config.kind) {
+                return Err(invalid(expected));
+            }
+
+            return store_path(question, config, path, answers);
+        }
+        (QuestionType::Text | QuestionType::Paragraph, toml::Value::String(answer)) => {
+            Answer::String(answer.clone())
+        }
+        (QuestionType::Computed, _) => return Err(invalid("no answer, the value is computed")),
+        _ => return Err(invalid("a string")),
+    };
+
+    answers.insert(question.to_string(), answer);
+
+    Ok(())
+}
+
 /// Reorders a topologically sorted list of nodes so that nodes with no incoming edges
 /// appear first in their original order.
 ///
@@ -274,6 +1082,100 @@ pub fn stablize_topological_order<Node: std::hash::Hash + Eq + Clone>(
     stable_order
 }
 
+/// Reorders a topologically sorted list of nodes so that nodes of the same group are contiguous
+/// wherever their dependencies allow.
+///
+/// Each group is placed where its first member appears in `sorted`, and nodes without a group keep
+/// their position, so the order is unchanged when no node has a group.
+pub fn group_topological_order<Node, Group>(
+    graph: &Graph<Node>,
+    sorted: Vec<Node>,
+    group_of: impl Fn(&Node) -> Option<Group>,
+) -> Vec<Node>
+where
+    Node: std::hash::Hash + Eq + Clone,
+    Group: std::hash::Hash + Eq,
+{
+    let position: HashMap<&Node, usize> = sorted
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node, index))
+        .collect();
+
+    // a group ranks at the position of its first member
+    let mut group_rank: HashMap<Group, usize> = HashMap::new();
+    for (index, node) in sorted.iter().enumerate() {
+        if let Some(group) = group_of(node) {
+            group_rank.entry(group).or_insert(index);
+        }
+    }
+    let rank = |node: &Node| {
+        let index = position.get(node).copied().unwrap_or(usize::MAX);
+        let group = group_of(node).and_then(|group| group_rank.get(&group).copied());
+
+        (group.unwrap_or(index), index)
+    };
+
+    let mut in_degrees: HashMap<&Node, usize> = sorted.iter().map(|node| (node, 0)).collect();
+    for (src, dest) in &graph.edges {
+        if position.contains_key(src) {
+            if let Some(count) = in_degrees.get_mut(dest) {
+                *count += 1;
+            }
+        }
+    }
+
+    let mut remaining = sorted.iter().collect::<Vec<_>>();
+    let mut ordered = Vec::with_capacity(sorted.len());
+    let mut current_group: Option<Group> = None;
+
+    while !remaining.is_empty() {
+        let available = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| in_degrees.get(**node).copied().unwrap_or(0) == 0);
+
+        // stay within the current group while any of its members are available
+        let next = available
+            .clone()
+            .filter(|(_, node)| current_group.is_some() && group_of(node) == current_group)
+            .min_by_key(|(_, node)| rank(node))
+            .or_else(|| available.min_by_key(|(_, node)| rank(node)))
+            .map(|(index, _)| index);
+
+        let Some(index) = next else {
+            // unreachable for a topologically sorted input, keep the rest as-is
+            ordered.extend(remaining.into_iter().cloned());
+            break;
+        };
+
+        let node = remaining.remove(index);
+        for (_, dest) in graph.edges.iter().filter(|(src, _)| src == node) {
+            if let Some(count) = in_degrees.get_mut(dest) {
+                *count = count.saturating_sub(1);
+            }
+        }
+
+        current_group = group_of(node);
+        ordered.push(node.clone());
+    }
+
+    ordered
+}
+
+/// Prints the heading shown before the first question of a [`Group`].
+fn print_group_heading(group: &Group) {
+    println!(
+        "\n{} {}",
+        "┌─".bold().bright_blue(),
+        group.title.bold().bright_blue()
+    );
+
+    if let Some(description) = &group.description {
+        println!("{} {}", "│".bright_blue(), description.dimmed());
+    }
+}
+
 /// Checks whether a dependency condition is satisfied based on previous answers.
 /// The dependency string should be in the format "question:expected_value".
 fn check_dependency(dep: &str, answers: &IndexMap<String, Answer>) -> bool {
@@ -284,6 +1186,10 @@ fn check_dependency(dep: &str, answers: &IndexMap<String, Answer>) -> bool {
                 Answer::String(ans) => ans == expected,
                 Answer::Bool(ans) => Ok(*ans) == expected.parse::<bool>(),
                 Answer::Array(arr) => arr.contains(&expected.to_string()),
+                Answer::Secret(ans) => ans.expose() == expected,
+                Answer::Value(ans) => expected
+                    .parse::<serde_json::Value>()
+                    .is_ok_and(|expected| *ans == expected),
             }
         } else {
             false
@@ -293,38 +1199,381 @@ fn check_dependency(dep: &str, answers: &IndexMap<String, Answer>) -> bool {
     }
 }
 
+/// Resolves the choices a select question offers, rendering `Dynamic` choices and running
+/// `choices_from` commands with `render`.
+fn resolve_choices<E>(
+    question: &str,
+    config: &Question,
+    answers: &IndexMap<String, Answer>,
+    render: &mut impl FnMut(&str, &str, &IndexMap<String, Answer>) -> Result<String, E>,
+) -> Result<Option<Vec<Choice>>, E>
+where
+    E: From<PromptError>,
+{
+    let choices = if let Some(choices_from) = &config.choices_from {
+        let command = choices_from
+            .command
+            .iter()
+            .map(|arg| render(question, arg, answers))
+            .collect::<Result<Vec<_>, E>>()?;
+        let display = command.join(" ");
+
+        let Some(mut process) = TaskCommand::Args(command).process() else {
+            return Err(PromptError::NoChoices {
+                question: question.to_string(),
+            }
+            .into());
+        };
+
+        let output = process
+            .output()
+            .map_err(|error| PromptError::ChoicesCommand {
+                question: question.to_string(),
+                command: display.clone(),
+                source: error,
+            })?;
+
+        if !output.status.success() {
+            return Err(PromptError::ChoicesCommandFailed {
+                question: question.to_string(),
+                command: display,
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            }
+            .into());
+        }
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| Choice::Value(line.to_string()))
+            .collect()
+    } else {
+        match &config.choices {
+            Some(Choices::Static(choices)) => choices.clone(),
+            Some(Choices::Dynamic(expression)) => {
+                let rendered = render(question, &Choices::template(expression), answers)?;
+                let invalid = || PromptError::InvalidChoices {
+                    question: question.to_string(),
+                    rendered: rendered.clone(),
+                };
+
+                // expressions are rendered as JSON, templates may render one choice per line
+                if !is_template(expression) || rendered.trim_start().starts_with('[') {
+                    serde_json::from_str::<Vec<Choice>>(&rendered).map_err(|_| invalid())?
+                } else {
+                    rendered
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(|line| Choice::Value(line.to_string()))
+                        .collect()
+                }
+            }
+            None => return Ok(None),
+        }
+    };
+
+    if choices.is_empty() {
+        return Err(PromptError::NoChoices {
+            question: question.to_string(),
+        }
+        .into());
+    }
+
+    Ok(Some(choices))
+}
+
 /// Processes the questions file and gathers user answers.
 ///
-/// This function reads a blueprint TOML file, constructs a dependency graph,
+/// This function takes the blueprint's [`QuestionsFile`], constructs a dependency graph,
 /// computes a topological order (with stabilization), and then prompts the user for answers
 /// based on each question's configuration and dependencies.
-pub fn get_answers(template_path: &Path) -> Result<IndexMap<String, Answer>, PromptError> {
-    let file = QuestionsFile::from_file(template_path.join("blueprint.toml"))?;
-    let nodes: Vec<String> = file.0.keys().cloned().collect();
+///
+/// `render` is called with the question name, one of its [`Question::templates`] and the answers
+/// gathered so far. `Computed` questions are not prompted, their `value` is evaluated and stored
+/// as their answer.
+///
+/// `choices_from` commands only run when [`PromptOptions::allow_commands`] is set; otherwise the
+/// first one found is reported before any question is asked.
+///
+/// Questions with an answer in [`PromptOptions::answers`] are not prompted. With
+/// [`PromptOptions::non_interactive`], nothing is prompted and answers are not reviewed.
+pub fn get_answers<E>(
+    file: QuestionsFile,
+    options: &PromptOptions,
+    mut render: impl FnMut(&str, &str, &IndexMap<String, Answer>) -> Result<String, E>,
+) -> Result<IndexMap<String, Answer>, E>
+where
+    E: From<PromptError>,
+{
+    if !options.allow_commands {
+        if let Some((question, choices_from)) = file
+            .questions
+            .iter()
+            .find_map(|(key, config)| config.choices_from.as_ref().map(|from| (key, from)))
+        {
+            return Err(PromptError::UntrustedCommand {
+                question: question.clone(),
+                command: choices_from.command.join(" "),
+            }
+            .into());
+        }
+    }
+
+    let nodes: Vec<String> = file.questions.keys().cloned().collect();
     let edges = QuestionsFile::adjacency_list_from_file(file.clone());
     let graph = Graph { nodes, edges };
     let order = tampopo::sort_graph(&graph).map_err(PromptError::from_sort_error)?;
     let stablized_order = stablize_topological_order(&graph, order);
-    let questions = file.0;
+    let stablized_order = group_topological_order(&graph, stablized_order, |question| {
+        file.group_of(question).map(|(name, _)| name.clone())
+    });
+    let questions = &file.questions;
     let mut answers = IndexMap::new();
+    let mut current_group: Option<&String> = None;
 
-    for question_name in stablized_order {
-        if let Some(config) = questions.get(&question_name) {
-            let should_prompt = config.raw_dependency.as_ref().is_none_or(|dep| match dep {
-                Dependency::Condition(val) => check_dependency(val, &answers),
-                Dependency::And { all } => all.iter().all(|d| check_dependency(d, &answers)),
-                Dependency::Or { any } => any.iter().any(|d| check_dependency(d, &answers)),
-            });
+    for question_name in &stablized_order {
+        if let Some(config) = questions.get(question_name) {
+            if !file.is_enabled(question_name, &answers) {
+                continue;
+            }
+
+            if !matches!(config.r#type, QuestionType::Computed) && !options.non_interactive {
+                let group = file.group_of(question_name);
 
-            if should_prompt {
-                try_prompt(&question_name, config, &mut answers)?;
+                if let Some((name, group)) = group {
+                    if current_group != Some(name) {
+                        print_group_heading(group);
+                    }
+                }
+
+                current_group = group.map(|(name, _)| name);
             }
+
+            answer_question(question_name, config, options, &mut answers, &mut render)?;
         }
     }
 
+    if options.non_interactive {
+        log::debug!("answers: {:?}", answers);
+
+        return Ok(answers);
+    }
+
+    // let the user revisit any prompted answer before rendering
+    while let Some(question_name) =
+        pick_answer_to_edit(questions, &stablized_order, &answers, options)?
+    {
+        let Some(config) = questions.get(&question_name) else {
+            continue;
+        };
+
+        answer_question(&question_name, config, options, &mut answers, &mut render)?;
+
+        update_dependents(
+            &file,
+            &graph,
+            &stablized_order,
+            &question_name,
+            options,
+            &mut answers,
+            &mut render,
+        )?;
+    }
+
+    log::debug!("answers: {:?}", answers);
+
     Ok(answers)
 }
 
+/// Answers a single question, either by evaluating a `Computed` value or by prompting the user.
+fn answer_question<E>(
+    question_name: &str,
+    config: &Question,
+    options: &PromptOptions,
+    answers: &mut IndexMap<String, Answer>,
+    render: &mut impl FnMut(&str, &str, &IndexMap<String, Answer>) -> Result<String, E>,
+) -> Result<(), E>
+where
+    E: From<PromptError>,
+{
+    if let QuestionType::Computed = config.r#type {
+        let value = config
+            .value
+            .as_ref()
+            .ok_or_else(|| PromptError::MissingComputedValue {
+                question: question_name.to_string(),
+            })?;
+
+        let rendered = render(question_name, &Question::value_template(value), answers)?;
+
+        let answer = match is_template(value) {
+            true => Answer::String(rendered),
+            false => serde_json::from_str::<serde_json::Value>(&rendered)
+                .map(Answer::from)
+                .unwrap_or(Answer::String(rendered)),
+        };
+
+        answers.insert(question_name.to_string(), answer);
+    } else {
+        let choices = resolve_choices(question_name, config, answers, render)?;
+
+        if let Some(value) = options.answers.get(question_name) {
+            apply_answer(
+                question_name,
+                config,
+                value,
+                choices.as_deref(),
+                options,
+                answers,
+            )?;
+        } else if let Some(secret) = env_secret(question_name, config, |variable| {
+            std::env::var(variable).ok()
+        }) {
+            answers.insert(question_name.to_string(), Answer::Secret(secret));
+        } else if options.non_interactive {
+            return Err(PromptError::MissingAnswer {
+                question: question_name.to_string(),
+            }
+            .into());
+        } else {
+            try_prompt(question_name, config, choices, options, answers)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Brings every question depending on `edited`, directly or not, up to date with its new answer.
+///
+/// Disabled dependents lose their answer, computed ones are evaluated again, and the others are
+/// asked again when they have no answer yet, or when their answer is no longer one of their
+/// choices.
+fn update_dependents<E>(
+    file: &QuestionsFile,
+    graph: &Graph<String>,
+    order: &[String],
+    edited: &str,
+    options: &PromptOptions,
+    answers: &mut IndexMap<String, Answer>,
+    render: &mut impl FnMut(&str, &str, &IndexMap<String, Answer>) -> Result<String, E>,
+) -> Result<(), E>
+where
+    E: From<PromptError>,
+{
+    let downstream = downstream_of(graph, edited);
+
+    for dependent in order.iter().filter(|node| downstream.contains(*node)) {
+        let Some(config) = file.questions.get(dependent) else {
+            continue;
+        };
+
+        if !file.is_enabled(dependent, answers) {
+            answers.shift_remove(dependent);
+            answers.shift_remove(&format!("{}{}", dependent, Question::CONTENTS_SUFFIX));
+            continue;
+        }
+
+        if !matches!(config.r#type, QuestionType::Computed) {
+            let choices = resolve_choices(dependent, config, answers, render)?;
+            let is_choice = |value: &String| {
+                choices
+                    .as_ref()
+                    .is_none_or(|choices| choices.iter().any(|choice| choice.value() == value))
+            };
+
+            let is_valid = match answers.get(dependent) {
+                Some(Answer::String(value)) => is_choice(value),
+                Some(Answer::Array(values)) => values.iter().all(is_choice),
+                Some(_) => true,
+                None => false,
+            };
+
+            if is_valid {
+                continue;
+            }
+
+            answers.shift_remove(dependent);
+        }
+
+        answer_question(dependent, config, options, answers, render)?;
+    }
+
+    Ok(())
+}
+
+/// Returns every node reachable from `start` by following the graph's edges.
+fn downstream_of(graph: &Graph<String>, start: &str) -> HashSet<String> {
+    let mut downstream = HashSet::new();
+    let mut queue = vec![start.to_string()];
+
+    while let Some(node) = queue.pop() {
+        for (_, dest) in graph.edges.iter().filter(|(src, _)| *src == node) {
+            if downstream.insert(dest.clone()) {
+                queue.push(dest.clone());
+            }
+        }
+    }
+
+    downstream
+}
+
+/// Shows a summary of every prompted answer and asks which one, if any, to answer again.
+///
+/// Answers given up front in [`PromptOptions::answers`], e.g. those recorded in the project's
+/// `.kopye-answers.toml`, were not prompted and are not offered either.
+///
+/// Returns `None` once the user confirms the answers, or when there is nothing to edit.
+fn pick_answer_to_edit(
+    questions: &IndexMap<String, Question>,
+    order: &[String],
+    answers: &IndexMap<String, Answer>,
+    options: &PromptOptions,
+) -> Result<Option<String>, PromptError> {
+    let editable = order
+        .iter()
+        .filter(|name| !options.answers.contains_key(*name))
+        .filter(|name| {
+            questions
+                .get(*name)
+                .is_some_and(|config| !matches!(config.r#type, QuestionType::Computed))
+        })
+        .filter_map(|name| answers.get(name).map(|answer| (name, answer)))
+        .collect::<Vec<_>>();
+
+    if editable.is_empty() {
+        return Ok(None);
+    }
+
+    let confirm = String::from("Continue with these answers");
+    let choices = std::iter::once(confirm)
+        .chain(editable.iter().map(|(name, answer)| {
+            let answer = answer.to_string();
+            let mut lines = answer.lines();
+            let first_line = lines.next().unwrap_or_default();
+            let ellipsis = if lines.next().is_some() { " …" } else { "" };
+
+            format!("{}: {}{}", name, first_line, ellipsis)
+        }))
+        .collect::<Vec<_>>();
+
+    let question = String::from("Review answers, or select one to change it:");
+
+    let selected = Select::new(&question, choices)
+        .raw_prompt()
+        .map_err(|error| PromptError::Prompt {
+            question: question.to_string(),
+            source: error,
+        })?;
+
+    Ok(selected
+        .index
+        .checked_sub(1)
+        .map(|index| editable[index].0.clone()))
+}
+
 pub fn get_project(config: Source) -> Result<String, PromptError> {
     let choices = config.projects.keys().collect();
 
@@ -365,3 +1614,600 @@ pub fn apply_changes() -> Result<bool, PromptError> {
 
     Ok(answer.to_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    enum TestError {
+        Prompt(PromptError),
+        Render(String),
+    }
+    impl From<PromptError> for TestError {
+        fn from(error: PromptError) -> Self {
+            TestError::Prompt(error)
+        }
+    }
+
+    /// Answers `questions` without prompting, rendering templates the way blueprints do.
+    fn answer_all(
+        questions: &str,
+        given: &[(&str, toml::Value)],
+    ) -> Result<IndexMap<String, Answer>, TestError> {
+        let options = PromptOptions {
+            answers: given
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            non_interactive: true,
+            ..PromptOptions::default()
+        };
+
+        let mut tera = tera::Tera::default();
+        crate::filters::register(&mut tera);
+
+        get_answers(
+            QuestionsFile::parse(questions).unwrap(),
+            &options,
+            |_, input, answers| render(&mut tera, input, answers),
+        )
+    }
+
+    fn render(
+        tera: &mut tera::Tera,
+        input: &str,
+        answers: &IndexMap<String, Answer>,
+    ) -> Result<String, TestError> {
+        let mut ctx = tera::Context::new();
+        for (name, answer) in answers {
+            match answer {
+                Answer::String(value) => ctx.insert(name, value),
+                Answer::Bool(value) => ctx.insert(name, value),
+                Answer::Array(value) => ctx.insert(name, value),
+                Answer::Secret(value) => ctx.insert(name, value.expose()),
+                Answer::Value(value) => ctx.insert(name, value),
+            }
+        }
+
+        tera.render_str(input, &ctx)
+            .map_err(|error| TestError::Render(format!("{:?}", error)))
+    }
+
+    /// The values of the choices `question`, the only one of `questions`, offers.
+    fn choices_of(question: &str) -> Result<Vec<String>, TestError> {
+        let file = QuestionsFile::parse(&format!(
+            "version = 1\n[questions.q]\ntype = \"string\"\n{}",
+            question
+        ));
+        let config = &file.unwrap().questions["q"];
+        let answers = IndexMap::from([(String::from("os"), Answer::String(String::from("linux")))]);
+
+        let mut tera = tera::Tera::default();
+        let choices = resolve_choices("q", config, &answers, &mut |_, input, answers| {
+            render(&mut tera, input, answers)
+        })?;
+
+        Ok(choices
+            .unwrap_or_default()
+            .iter()
+            .map(|choice| choice.value().to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_computed_values_are_typed_expressions() {
+        let answers = answer_all(
+            r#"
+            version = 1
+
+            [questions.project]
+            type = "string"
+
+            [questions.slug]
+            type = "computed"
+            value = "project | kebab_case"
+
+            [questions.is_app]
+            type = "computed"
+            value = "slug is ending_with('app')"
+
+            [questions.title]
+            type = "computed"
+            value = "{{ slug }} v{{ 1 + 1 }}"
+
+            [questions.parts]
+            type = "computed"
+            value = "slug | split(pat='-')"
+
+            [questions.length]
+            type = "computed"
+            value = "slug | length"
+
+            [questions.readme]
+            type = "computed"
+            value = "not is_app"
+            "#,
+            &[("project", toml::Value::from("MyGreatLib"))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            answers["slug"],
+            Answer::String(String::from("my-great-lib"))
+        );
+        assert_eq!(answers["is_app"], Answer::Bool(false));
+        assert_eq!(
+            answers["title"],
+            Answer::String(String::from("my-great-lib v2"))
+        );
+        assert_eq!(
+            answers["parts"],
+            Answer::Array(vec![
+                String::from("my"),
+                String::from("great"),
+                String::from("lib")
+            ])
+        );
+        assert_eq!(answers["length"], Answer::Value(serde_json::json!(12)));
+        // a computed `false` must not be truthy for the values depending on it
+        assert_eq!(answers["readme"], Answer::Bool(true));
+
+        let missing = r#"
+            version = 1
+
+            [questions.slug]
+            type = "computed"
+            value = "project | kebab_case"
+            "#;
+        assert!(matches!(
+            answer_all(missing, &[]),
+            Err(TestError::Render(message)) if message.contains("project")
+        ));
+
+        let unanswered = r#"
+            version = 1
+
+            [questions.project]
+            type = "string"
+            "#;
+        assert!(matches!(
+            answer_all(unanswered, &[]),
+            Err(TestError::Prompt(PromptError::MissingAnswer { question })) if question == "project"
+        ));
+    }
+
+    #[test]
+    fn test_choices_read_labels_values_and_help() {
+        let questions = r#"
+            version = 1
+
+            [questions.license]
+            type = "string"
+            choices = [
+                "MIT",
+                { value = "Apache-2.0", label = "Apache 2.0", help = "patent grant" },
+                { value = "GPL-3.0" },
+            ]
+
+            [questions.notice]
+            type = "computed"
+            value = "license"
+            depends_on = "license:Apache-2.0"
+            "#;
+
+        let file = QuestionsFile::parse(questions).unwrap();
+        let Some(Choices::Static(choices)) = &file.questions["license"].choices else {
+            panic!("expected static choices");
+        };
+
+        let shown = choices.iter().map(Choice::to_string).collect::<Vec<_>>();
+        assert_eq!(shown, ["MIT", "Apache 2.0 - patent grant", "GPL-3.0"]);
+        let values = choices.iter().map(Choice::value).collect::<Vec<_>>();
+        assert_eq!(values, ["MIT", "Apache-2.0", "GPL-3.0"]);
+        assert!(matches!(
+            file.questions["license"].r#type,
+            QuestionType::Select
+        ));
+
+        // answers are the values of the choices, never their labels
+        let answers = answer_all(questions, &[("license", toml::Value::from("Apache-2.0"))]);
+        let answers = answers.unwrap();
+        assert_eq!(
+            answers["license"],
+            Answer::String(String::from("Apache-2.0"))
+        );
+        assert_eq!(
+            answers["notice"],
+            Answer::String(String::from("Apache-2.0"))
+        );
+
+        assert!(matches!(
+            answer_all(questions, &[("license", toml::Value::from("Apache 2.0"))]),
+            Err(TestError::Prompt(PromptError::InvalidAnswer { question, .. })) if question == "license"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_choices_parses_commands_json_and_lines() {
+        let choices = |question: &str| choices_of(question).unwrap();
+
+        assert_eq!(
+            choices(r#"choices_from = { command = ["printf", "a\n\n  b  \n{{ os }}"] }"#),
+            ["a", "b", "linux"]
+        );
+        assert_eq!(
+            choices(r#"choices = "[os, 'bsd'] | concat(with='mac')""#),
+            ["linux", "bsd", "mac"]
+        );
+        assert_eq!(
+            choices(r#"choices = '{% raw %}[{"value": "x", "label": "X"}, "y"]{% endraw %}'"#),
+            ["x", "y"]
+        );
+        assert_eq!(
+            choices(r#"choices = "{% for n in [1, 2] %}{{ os }}-{{ n }}\n{% endfor %}""#),
+            ["linux-1", "linux-2"]
+        );
+
+        assert!(matches!(
+            choices_of(r#"choices_from = { command = ["sh", "-c", "echo oops >&2; exit 3"] }"#),
+            Err(TestError::Prompt(PromptError::ChoicesCommandFailed { stderr, .. })) if stderr == "oops"
+        ));
+        assert!(matches!(
+            choices_of(r#"choices_from = { command = ["kopye-missing-program"] }"#),
+            Err(TestError::Prompt(PromptError::ChoicesCommand { .. }))
+        ));
+        assert!(matches!(
+            choices_of(r#"choices_from = { command = ["true"] }"#),
+            Err(TestError::Prompt(PromptError::NoChoices { .. }))
+        ));
+        assert!(matches!(
+            choices_of(r#"choices_from = { command = [] }"#),
+            Err(TestError::Prompt(PromptError::NoChoices { .. }))
+        ));
+        assert!(matches!(
+            choices_of(r#"choices = "[] | concat(with=missing)""#),
+            Err(TestError::Render(_))
+        ));
+        assert!(matches!(
+            choices_of(r#"choices = "os | upper""#),
+            Err(TestError::Prompt(PromptError::InvalidChoices { question, rendered }))
+                if question == "q" && rendered == "\"LINUX\""
+        ));
+        assert!(matches!(
+            choices_of(r#"choices = "[{{ os }}]""#),
+            Err(TestError::Prompt(PromptError::InvalidChoices { .. }))
+        ));
+
+        // commands only run once trusted
+        assert!(matches!(
+            answer_all(
+                r#"
+                version = 1
+
+                [questions.q]
+                type = "string"
+                choices_from = { command = ["echo", "a"] }
+                "#,
+                &[]
+            ),
+            Err(TestError::Prompt(PromptError::UntrustedCommand { question, .. })) if question == "q"
+        ));
+    }
+
+    #[test]
+    fn test_passwords_are_read_from_their_environment_variable() {
+        let questions = r#"
+            version = 1
+
+            [questions.token]
+            type = "password"
+
+            [questions.deploy_key]
+            type = "password"
+            env = "DEPLOY_KEY"
+
+            [questions.name]
+            type = "string"
+            "#;
+        let file = QuestionsFile::parse(questions).unwrap();
+
+        let environment = IndexMap::from([
+            ("KOPYE_TOKEN", "abc"),
+            ("DEPLOY_KEY", "def"),
+            ("KOPYE_NAME", "ghi"),
+        ]);
+        let lookup = |variable: &str| environment.get(variable).map(|value| value.to_string());
+        let secret = |name: &str| {
+            env_secret(name, &file.questions[name], lookup)
+                .map(|secret| secret.expose().to_string())
+        };
+
+        assert_eq!(secret("token").as_deref(), Some("abc"));
+        assert_eq!(secret("deploy_key").as_deref(), Some("def"));
+        // only passwords are read from the environment
+        assert_eq!(secret("name"), None);
+
+        // without its variable, a password still needs an answer
+        assert!(env_secret("deploy_key", &file.questions["deploy_key"], |_| None).is_none());
+    }
+
+    #[test]
+    fn test_path_answers_are_checked_resolved_and_read() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("LICENSE"), "MIT").unwrap();
+
+        let questions = |extra: &str| {
+            format!(
+                "version = 1\n[questions.license]\ntype = \"path\"\nrelative_to = \"destination\"\n{}",
+                extra
+            )
+        };
+        let answer = |questions: &str, value: &str| {
+            let file = QuestionsFile::parse(questions).unwrap();
+            let options = PromptOptions {
+                destination: directory.path().to_path_buf(),
+                ..PromptOptions::default()
+            };
+            let mut answers = IndexMap::new();
+
+            apply_answer(
+                "license",
+                &file.questions["license"],
+                &toml::Value::from(value),
+                None,
+                &options,
+                &mut answers,
+            )
+            .map(|_| answers)
+        };
+        let license = directory.path().join("LICENSE").display().to_string();
+
+        // relative to the destination, and read
+        let answers = answer(&questions("read = true"), "sub/../LICENSE").unwrap();
+        assert_eq!(answers["license"], Answer::String(license.clone()));
+        assert_eq!(
+            answers["license_contents"],
+            Answer::String(String::from("MIT"))
+        );
+
+        // absolute paths are kept as they are
+        let answers = answer(&questions("kind = \"file\""), &license).unwrap();
+        assert_eq!(answers["license"], Answer::String(license.clone()));
+        assert!(!answers.contains_key("license_contents"));
+
+        let missing = answer(&questions("must_exist = true"), "NOTICE");
+        assert!(matches!(
+            missing,
+            Err(PromptError::InvalidAnswer { expected, .. }) if expected == "an existing path"
+        ));
+        let not_a_dir = answer(&questions("kind = \"dir\""), "LICENSE");
+        assert!(matches!(
+            not_a_dir,
+            Err(PromptError::InvalidAnswer { expected, .. }) if expected == "a directory"
+        ));
+        assert!(matches!(
+            answer(&questions("read = true"), "NOTICE"),
+            Err(PromptError::Io(_))
+        ));
+        // a path that may be created later
+        assert!(answer(&questions("kind = \"dir\""), "docs").is_ok());
+    }
+
+    #[test]
+    fn test_update_dependents_asks_again_for_stale_choices() {
+        let file = QuestionsFile::parse(
+            r#"
+            version = 1
+
+            [questions.os]
+            type = "string"
+            choices = ["linux", "mac"]
+
+            [questions.arch]
+            type = "string"
+            choices = "{% if os == 'linux' %}x86_64\naarch64{% else %}aarch64{% endif %}"
+
+            [questions.target]
+            type = "string"
+            choices = "[arch ~ '-' ~ os, arch ~ '-' ~ os ~ '-musl']"
+
+            [questions.summary]
+            type = "computed"
+            value = "target | upper"
+            "#,
+        )
+        .unwrap();
+        let graph = Graph {
+            nodes: file.questions.keys().cloned().collect(),
+            edges: QuestionsFile::adjacency_list_from_file(file.clone()),
+        };
+        let order = file.questions.keys().cloned().collect::<Vec<_>>();
+
+        let answer = |value: &str| Answer::String(value.to_string());
+        let mut answers = IndexMap::from([
+            (String::from("os"), answer("mac")),
+            (String::from("arch"), answer("x86_64")),
+            (String::from("target"), answer("x86_64-linux")),
+            (String::from("summary"), answer("X86_64-LINUX")),
+        ]);
+        // stands for the user, picking the only architecture left and then a target
+        let options = PromptOptions {
+            answers: IndexMap::from([
+                (String::from("arch"), toml::Value::from("aarch64")),
+                (
+                    String::from("target"),
+                    toml::Value::from("aarch64-mac-musl"),
+                ),
+            ]),
+            non_interactive: true,
+            ..PromptOptions::default()
+        };
+
+        let mut tera = tera::Tera::default();
+        crate::filters::register(&mut tera);
+        let mut render =
+            |_: &str, input: &str, answers: &IndexMap<_, _>| render(&mut tera, input, answers);
+
+        update_dependents(
+            &file,
+            &graph,
+            &order,
+            "os",
+            &options,
+            &mut answers,
+            &mut render,
+        )
+        .unwrap();
+
+        assert_eq!(answers["arch"], answer("aarch64"));
+        assert_eq!(answers["target"], answer("aarch64-mac-musl"));
+        assert_eq!(answers["summary"], answer("AARCH64-MAC-MUSL"));
+
+        // answers still among the choices are kept
+        answers.insert(String::from("target"), answer("aarch64-mac"));
+        update_dependents(
+            &file,
+            &graph,
+            &order,
+            "arch",
+            &PromptOptions::default(),
+            &mut answers,
+            &mut render,
+        )
+        .unwrap();
+        assert_eq!(answers["target"], answer("aarch64-mac"));
+        assert_eq!(answers["summary"], answer("AARCH64-MAC"));
+    }
+
+    #[test]
+    fn test_group_topological_order_keeps_groups_contiguous() {
+        let nodes = vec!["project", "ci", "docs", "ci_provider", "license"];
+        let edges = vec![("ci", "ci_provider")];
+        let graph: Graph<&str> = Graph {
+            nodes: nodes.clone(),
+            edges,
+        };
+        let group_of = |node: &&str| match *node {
+            "ci" | "ci_provider" => Some("ci"),
+            _ => None,
+        };
+
+        let ordered = group_topological_order(&graph, nodes, group_of);
+
+        assert_eq!(
+            ordered,
+            vec!["project", "ci", "ci_provider", "docs", "license"]
+        );
+    }
+
+    #[test]
+    fn test_group_topological_order_is_unchanged_without_groups() {
+        let nodes = vec!["a", "b", "c"];
+        let graph: Graph<&str> = Graph {
+            nodes: nodes.clone(),
+            edges: vec![("c", "b")],
+        };
+        let sorted = vec!["a", "c", "b"];
+
+        let ordered = group_topological_order(&graph, sorted.clone(), |_| None::<&str>);
+
+        assert_eq!(ordered, sorted);
+    }
+
+    #[test]
+    fn test_questions_file_reads_groups_and_questions_in_order() {
+        let file = QuestionsFile::parse(
+            r#"
+            version = 1
+
+            [questions.project]
+            type = "Text"
+            help = "Name of project"
+
+            [groups.ci]
+            title = "Continuous integration"
+            depends_on = "project:kopye"
+
+            [questions.ci_provider]
+            type = "Select"
+            help = "Provider"
+            choices = ["github", "gitlab"]
+            group = "ci"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            file.questions.keys().collect::<Vec<_>>(),
+            vec!["project", "ci_provider"]
+        );
+        assert!(file.group_of("ci_provider").is_some());
+    }
+
+    #[test]
+    fn test_legacy_questions_file_reserves_no_names() {
+        let file = QuestionsFile::parse(
+            r#"
+            [project]
+            type = "Text"
+            help = "Name of project"
+
+            [groups]
+            type = "MultiSelect"
+            help = "Unix groups of the service user"
+            choices = ["www-data", "docker"]
+
+            [tasks]
+            type = "Text"
+            help = "Makefile targets"
+
+            [git]
+            type = "Confirm"
+            help = "Initialize a repository?"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(file.version, 0);
+        assert_eq!(
+            file.questions.keys().collect::<Vec<_>>(),
+            vec!["project", "groups", "tasks", "git"]
+        );
+        assert!(file.groups.is_empty() && file.tasks.is_empty() && file.git.is_none());
+    }
+
+    #[test]
+    fn test_questions_file_reads_versioned_layout_and_aliases() {
+        let file = QuestionsFile::parse(
+            r#"
+            version = 1
+
+            [settings]
+            undefined = "lenient"
+
+            [questions.version]
+            type = "string"
+            help = "Release version"
+
+            [questions.target]
+            type = "string"
+            help = "Compilation targets"
+            choices = ["x86_64-apple-darwin", "aarch64-apple-darwin"]
+            multiselect = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(file.version, 1);
+        assert_eq!(file.settings.undefined, Some(UndefinedBehavior::Lenient));
+        assert!(matches!(
+            file.questions["version"].r#type,
+            QuestionType::Text
+        ));
+        assert!(matches!(
+            file.questions["target"].r#type,
+            QuestionType::MultiSelect
+        ));
+    }
+}
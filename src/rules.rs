@@ -0,0 +1,342 @@
+use crate::{
+    errors::{FileOperation, IoError},
+    prompt::QuestionsFile,
+};
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use miette::Diagnostic;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum RulesError {
+    #[error("I/O error within rules domain")]
+    #[diagnostic(code(kopye::rules::io))]
+    Io(#[from] IoError),
+
+    #[error("Invalid pattern in '{path}'")]
+    #[diagnostic(
+        code(kopye::rules::invalid_pattern),
+        help("Patterns follow the .gitignore syntax")
+    )]
+    InvalidPattern {
+        path: PathBuf,
+        #[source]
+        source: ignore::Error,
+    },
+
+    #[error("Invalid [files] pattern `{pattern}` in '{path}'")]
+    #[diagnostic(
+        code(kopye::rules::invalid_glob),
+        help("Keys of the [files] table are globs relative to the blueprint, e.g. `ci/*.yml`")
+    )]
+    InvalidGlob {
+        pattern: String,
+        path: PathBuf,
+        #[source]
+        source: globset::Error,
+    },
+
+    #[error("Invalid delete pattern `{pattern}` in '{path}'")]
+    #[diagnostic(
+        code(kopye::rules::invalid_delete),
+        help("`delete` lists globs relative to the generated project, e.g. `docs/**`")
+    )]
+    InvalidDelete {
+        pattern: String,
+        path: PathBuf,
+        #[source]
+        source: globset::Error,
+    },
+}
+
+/// Name of the file listing blueprint files to exclude, in the `.gitignore` syntax.
+pub const KOPYEIGNORE: &str = ".kopyeignore";
+
+/// Always excluded from a blueprint, on top of its own patterns, along with the answer sets and
+/// snapshots of `kopye test`.
+const DEFAULT_EXCLUDES: [&str; 4] = [
+    ".git/",
+    "/.kopyeignore",
+    "/tests/*.toml",
+    "/tests/snapshots/",
+];
+
+/// Escapes braces, which the `.gitignore` syntax does not use for alternatives either, so that
+/// patterns such as `{{cookiecutter.name}}/` match literally.
+fn literal_braces(pattern: &str) -> String {
+    pattern.replace('{', "\\{").replace('}', "\\}")
+}
+
+/// Compiles a glob whose `*` stays within a path segment.
+fn glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    let glob = GlobBuilder::new(&literal_braces(pattern))
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()?;
+
+    Ok(glob.compile_matcher())
+}
+
+/// An entry of the `[files]` table of `blueprint.toml`, keyed by a glob matching files or
+/// directories relative to the blueprint, e.g.
+/// `"ci/github.yml" = { when = "ci == 'github'", to = ".github/workflows/ci.yml" }`.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct FileSettings {
+    /// Name of an array answer; the entry is rendered once per item, with `item` and `loop`
+    /// (`index`, `index0`, `first`, `last` and `length`) set in its path and contents
+    #[serde(default)]
+    pub each: Option<String>,
+    /// A tera expression; the entry, and its children, are only rendered when it is truthy
+    #[serde(default)]
+    pub when: Option<String>,
+    /// Destination of the entry relative to the generated project, rendered by tera, instead of
+    /// its rendered path
+    #[serde(default)]
+    pub to: Option<String>,
+}
+impl FileSettings {
+    /// The template rendering [`FileSettings::when`] to `true` when the entry is rendered.
+    pub fn condition(&self) -> Option<String> {
+        self.when
+            .as_ref()
+            .map(|when| format!("{{% if {} %}}true{{% endif %}}", when))
+    }
+
+    /// Every tera template of the entry.
+    pub fn templates(&self) -> Vec<String> {
+        self.each
+            .iter()
+            .map(|each| format!("{{{{ {} }}}}", each))
+            .chain(self.condition())
+            .chain(self.to.clone())
+            .collect()
+    }
+}
+
+/// Which files of a blueprint are left out, which are copied without being rendered, and how the
+/// others are repeated, conditioned or renamed.
+///
+/// Exclusions and copies come from `.gitignore`-style patterns, relative to the blueprint
+/// directory: `exclude` and `copy_only` under `[settings]` of `blueprint.toml`, and a
+/// `.kopyeignore` file for excludes. The rest comes from its `[files]` table, where the first
+/// glob matching an entry applies. Its `delete` globs leave out files generated by the blueprint
+/// it extends.
+#[derive(Debug, Clone)]
+pub struct FileRules {
+    exclude: Gitignore,
+    copy_only: Gitignore,
+    files: Vec<(String, GlobMatcher, FileSettings)>,
+    /// Files of the extended blueprint left out, matched against their destination
+    deleted: Vec<GlobMatcher>,
+    /// Directories left out whatever the patterns say, e.g. shared templates
+    directories: Vec<PathBuf>,
+}
+impl FileRules {
+    pub fn new(blueprint_directory: &Path, questions: &QuestionsFile) -> Result<Self, RulesError> {
+        let settings = &questions.settings;
+        let blueprint_toml = blueprint_directory.join("blueprint.toml");
+        let invalid = |path: &Path| {
+            let path = path.to_path_buf();
+
+            move |source| RulesError::InvalidPattern { path, source }
+        };
+
+        let mut exclude = GitignoreBuilder::new(blueprint_directory);
+
+        for pattern in DEFAULT_EXCLUDES {
+            exclude
+                .add_line(None, pattern)
+                .expect("default excludes are valid");
+        }
+        for pattern in &settings.exclude {
+            exclude
+                .add_line(Some(blueprint_toml.clone()), &literal_braces(pattern))
+                .map_err(invalid(&blueprint_toml))?;
+        }
+
+        let kopyeignore = blueprint_directory.join(KOPYEIGNORE);
+        if kopyeignore.is_file() {
+            let content = std::fs::read_to_string(&kopyeignore)
+                .map_err(|error| IoError::new(FileOperation::Read, kopyeignore.clone(), error))?;
+
+            for line in content.lines() {
+                exclude
+                    .add_line(Some(kopyeignore.clone()), &literal_braces(line))
+                    .map_err(invalid(&kopyeignore))?;
+            }
+        }
+
+        let mut copy_only = GitignoreBuilder::new(blueprint_directory);
+
+        for pattern in &settings.copy_only {
+            copy_only
+                .add_line(Some(blueprint_toml.clone()), &literal_braces(pattern))
+                .map_err(invalid(&blueprint_toml))?;
+        }
+
+        let files = questions
+            .files
+            .iter()
+            .map(|(pattern, settings)| {
+                let glob = glob(pattern).map_err(|source| RulesError::InvalidGlob {
+                    pattern: pattern.clone(),
+                    path: blueprint_toml.clone(),
+                    source,
+                })?;
+
+                Ok((pattern.clone(), glob, settings.clone()))
+            })
+            .collect::<Result<_, RulesError>>()?;
+
+        let deleted = questions
+            .delete
+            .iter()
+            .map(|pattern| {
+                glob(pattern).map_err(|source| RulesError::InvalidDelete {
+                    pattern: pattern.clone(),
+                    path: blueprint_toml.clone(),
+                    source,
+                })
+            })
+            .collect::<Result<_, RulesError>>()?;
+
+        Ok(Self {
+            exclude: exclude.build().map_err(invalid(&blueprint_toml))?,
+            copy_only: copy_only.build().map_err(invalid(&blueprint_toml))?,
+            files,
+            deleted,
+            directories: Vec::new(),
+        })
+    }
+
+    /// Also excludes `directory`, relative to the blueprint, along with everything inside it.
+    pub fn exclude_directory(mut self, directory: PathBuf) -> Self {
+        if directory.components().next().is_some() {
+            self.directories.push(directory);
+        }
+
+        self
+    }
+
+    /// Whether the entry at `relative`, or any of its parents, is excluded from the blueprint.
+    pub fn is_excluded(&self, relative: &Path, is_dir: bool) -> bool {
+        self.directories
+            .iter()
+            .any(|directory| relative.starts_with(directory))
+            || self
+                .exclude
+                .matched_path_or_any_parents(relative, is_dir)
+                .is_ignore()
+    }
+
+    /// Whether the entry at `relative`, or any of its parents, is copied verbatim: neither its
+    /// name nor its contents are rendered.
+    pub fn is_copy_only(&self, relative: &Path, is_dir: bool) -> bool {
+        self.copy_only
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+    }
+
+    /// Whether an inherited entry rendered to `destination`, or any of its parents, is deleted.
+    pub fn is_deleted(&self, destination: &Path) -> bool {
+        destination
+            .ancestors()
+            .any(|ancestor| self.deleted.iter().any(|glob| glob.is_match(ancestor)))
+    }
+
+    /// The settings of the first `[files]` glob matching the entry at `relative`.
+    pub fn settings(&self, relative: &Path) -> Option<&FileSettings> {
+        self.files
+            .iter()
+            .find(|(_, glob, _)| glob.is_match(relative))
+            .map(|(_, _, settings)| settings)
+    }
+
+    /// The answer the entry at `relative` is repeated for, if any.
+    pub fn each(&self, relative: &Path) -> Option<&str> {
+        self.settings(relative)?.each.as_deref()
+    }
+
+    /// Whether the entry at `relative`, or any of its parents, is repeated for an answer.
+    pub fn in_loop(&self, relative: &Path) -> bool {
+        relative
+            .ancestors()
+            .any(|ancestor| self.each(ancestor).is_some())
+    }
+
+    /// Every `[files]` glob along with its settings, in file order.
+    pub fn files(&self) -> impl Iterator<Item = (&str, &FileSettings)> {
+        self.files
+            .iter()
+            .map(|(pattern, _, settings)| (pattern.as_str(), settings))
+    }
+
+    /// The `[files]` globs matching none of `paths`.
+    pub fn unmatched_files<'a>(&'a self, paths: &[PathBuf]) -> Vec<&'a str> {
+        self.files
+            .iter()
+            .filter(|(_, glob, _)| !paths.iter().any(|path| glob.is_match(path)))
+            .map(|(pattern, _, _)| pattern.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_rules_match_settings_and_kopyeignore() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join(KOPYEIGNORE), "*.swp\n").unwrap();
+
+        let questions = QuestionsFile::parse(
+            r#"
+            version = 1
+
+            [settings]
+            exclude = ["target/"]
+            copy_only = ["{{cookiecutter.name}}/", "*.png"]
+
+            [files."services/{{ item }}"]
+            each = "services"
+
+            [files]
+            "ci/*.yml" = { when = "ci", to = ".github/workflows/{{ ci }}.yml" }
+            "ci/**" = { when = "false" }
+            "#,
+        )
+        .unwrap();
+        let rules = FileRules::new(directory.path(), &questions).unwrap();
+
+        assert!(rules.is_excluded(Path::new(".git/config"), false));
+        assert!(rules.is_excluded(Path::new(KOPYEIGNORE), false));
+        assert!(rules.is_excluded(Path::new("target/debug/app"), false));
+        assert!(rules.is_excluded(Path::new("src/.main.rs.swp"), false));
+        assert!(!rules.is_excluded(Path::new("src/main.rs"), false));
+        // `kopye test` fixtures, but not the tests of the generated project
+        assert!(rules.is_excluded(Path::new("tests/minimal.toml"), false));
+        assert!(rules.is_excluded(Path::new("tests/snapshots/minimal/README.md"), false));
+        assert!(!rules.is_excluded(Path::new("tests/cli.rs.tera"), false));
+        assert!(!rules.is_excluded(Path::new("tests/fixtures/config.toml"), false));
+
+        assert!(rules.is_copy_only(Path::new("{{cookiecutter.name}}"), true));
+        assert!(rules.is_copy_only(Path::new("{{cookiecutter.name}}/setup.py"), false));
+        assert!(rules.is_copy_only(Path::new("assets/logo.png"), false));
+        assert!(!rules.is_copy_only(Path::new("README.md.tera"), false));
+
+        assert_eq!(
+            rules.each(Path::new("services/{{ item }}")),
+            Some("services")
+        );
+        assert!(rules.in_loop(Path::new("services/{{ item }}/main.rs.tera")));
+        assert!(!rules.in_loop(Path::new("services")));
+
+        let ci = rules.settings(Path::new("ci/github.yml")).unwrap();
+        assert_eq!(ci.to.as_deref(), Some(".github/workflows/{{ ci }}.yml"));
+        let nested = rules.settings(Path::new("ci/nested/gitlab.yml")).unwrap();
+        assert_eq!(nested.when.as_deref(), Some("false"));
+    }
+}
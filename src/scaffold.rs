@@ -0,0 +1,340 @@
+use crate::{
+    errors::{FileOperation, IoError},
+    snapshot::{self, SnapshotError, TestOptions},
+    source::{Source, SourceError},
+};
+use colored::Colorize;
+use miette::Diagnostic;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum ScaffoldError {
+    #[error("I/O error within scaffold domain")]
+    #[diagnostic(code(kopye::scaffold::io))]
+    Io(#[from] IoError),
+
+    #[error("'{path}' already exists")]
+    #[diagnostic(
+        code(kopye::scaffold::source_exists),
+        help("Use `kopye add-blueprint <name>` to add a blueprint to an existing source")
+    )]
+    SourceExists { path: PathBuf },
+
+    #[error("'{path}' already exists, `kopye init` would overwrite it")]
+    #[diagnostic(
+        code(kopye::scaffold::path_exists),
+        help("Run `kopye init` in another directory, or move the existing files out of the way")
+    )]
+    PathExists { path: PathBuf },
+
+    #[error("Blueprint `{name}` is already registered in '{path}'")]
+    #[diagnostic(code(kopye::scaffold::blueprint_exists))]
+    BlueprintExists { name: String, path: PathBuf },
+
+    #[error("Unable to parse toml file at '{path}': {source}")]
+    #[diagnostic(code(kopye::scaffold::parse_toml), help("Review toml file"))]
+    ParseToml {
+        path: PathBuf,
+        #[source]
+        source: Box<toml_edit::TomlError>,
+    },
+
+    #[error("Path '{path}' is not valid UTF-8")]
+    #[diagnostic(code(kopye::scaffold::invalid_path))]
+    InvalidPath { path: PathBuf },
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Source(#[from] SourceError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Snapshot(#[from] Box<SnapshotError>),
+}
+
+const BLUEPRINTS_TOML: &str = "blueprints.toml";
+const EXAMPLE_BLUEPRINT: &str = "example";
+
+/// Files of the source created by `kopye init`, as `(path, contents)`.
+const SOURCE_FILES: [(&str, &str); 8] = [
+    (
+        BLUEPRINTS_TOML,
+        r#"# Every blueprint of this source, by name: `kopye copy <source> example <destination>`
+[example]
+path = "example"
+"#,
+    ),
+    (
+        "example/blueprint.toml",
+        r#"version = 1
+
+[settings]
+# fail when a template uses a variable no question defines
+undefined = "strict"
+
+[groups.project]
+title = "Project"
+description = "What the project is called and what it does"
+
+[questions.name]
+type = "string"
+help = "Name of the project"
+group = "project"
+
+[questions.description]
+type = "paragraph"
+help = "A short description, written in your editor"
+group = "project"
+
+[questions.crate_name]
+type = "computed"
+value = "{{ name | crate_name }}"
+
+[questions.license]
+type = "select"
+help = "License of the project"
+choices = ["MIT", "Apache-2.0", { value = "none", label = "No license" }]
+
+[questions.features]
+type = "multiselect"
+help = "Optional features"
+choices = ["cli", "logging"]
+
+[questions.ci]
+type = "bool"
+help = "Add a GitHub Actions workflow?"
+
+[questions.api_token]
+type = "password"
+help = "Token written to .env, can also be set with $KOPYE_API_TOKEN"
+
+[questions.docs]
+type = "path"
+help = "Directory holding the documentation"
+kind = "dir"
+relative_to = "destination"
+depends_on = "ci:true"
+"#,
+    ),
+    (
+        "example/README.md.tera",
+        r#"# {{ name }}
+
+{{ description }}
+
+The crate is named `{{ crate_name }}`.
+{% if features %}
+Features: {{ features | join(sep=", ") }}
+{% endif %}{% if license != "none" %}
+Licensed under {{ license }}.
+{% endif %}"#,
+    ),
+    ("example/.gitignore", ".env\n"),
+    ("example/.env.tera", "API_TOKEN={{ api_token }}\n"),
+    (
+        // the whole `.github` directory is skipped when the segment renders to nothing
+        "example/{% if ci %}.github{% endif %}/workflows/ci.yml.tera",
+        r#"name: {{ name }}
+
+on: [push]
+
+jobs:
+  docs:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - run: ls {% if docs %}docs{% endif %}
+"#,
+    ),
+    (
+        "example/tests/default.toml",
+        r#"# Answers used by `kopye test`, compared with example/tests/snapshots/default
+name = "My Project"
+description = "An example project"
+license = "MIT"
+features = ["cli"]
+ci = true
+api_token = "not-a-real-token"
+docs = "docs"
+"#,
+    ),
+    (
+        "example/tests/no-ci.toml",
+        r#"name = "Minimal"
+description = "Without a workflow"
+license = "none"
+features = []
+ci = false
+api_token = "not-a-real-token"
+"#,
+    ),
+];
+
+/// `blueprint.toml` of a blueprint created by `kopye add-blueprint`.
+const NEW_BLUEPRINT_TOML: &str = r#"version = 1
+
+[questions.name]
+type = "string"
+help = "Name of the project"
+"#;
+
+fn write(path: &Path, contents: &str) -> Result<(), IoError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|error| IoError::new(FileOperation::Mkdir, parent.into(), error))?;
+    }
+
+    fs::write(path, contents)
+        .map_err(|error| IoError::new(FileOperation::Write, path.into(), error))?;
+
+    println!("{} {}", "create".green(), path.display());
+
+    Ok(())
+}
+
+fn to_str(path: &Path) -> Result<&str, ScaffoldError> {
+    path.to_str().ok_or_else(|| ScaffoldError::InvalidPath {
+        path: path.to_path_buf(),
+    })
+}
+
+/// Creates a working blueprint source in `directory`: a `blueprints.toml`, an example blueprint
+/// using every question type, and `kopye test` fixtures along with their snapshots.
+///
+/// Nothing is written when any of these already exists, other files of `directory` are kept.
+pub fn init_source(directory: &Path) -> Result<(), ScaffoldError> {
+    let blueprints_toml = directory.join(BLUEPRINTS_TOML);
+
+    if blueprints_toml.exists() {
+        return Err(ScaffoldError::SourceExists {
+            path: blueprints_toml,
+        });
+    }
+
+    // the files are created along with the directories holding them, e.g. `example`
+    let existing = SOURCE_FILES
+        .iter()
+        .filter_map(|(path, _)| Path::new(path).iter().next())
+        .map(|created| directory.join(created))
+        .find(|path| path.exists());
+
+    if let Some(path) = existing {
+        return Err(ScaffoldError::PathExists { path });
+    }
+
+    for (path, contents) in SOURCE_FILES {
+        write(&directory.join(path), contents)?;
+    }
+
+    // record the snapshots, so that `kopye test` passes right away
+    let source = Source::build_from(to_str(directory)?)?;
+    let options = TestOptions {
+        update: true,
+        ..Default::default()
+    };
+
+    snapshot::test_source(&source, &options).map_err(Box::new)?;
+
+    println!(
+        "\nTry it with `kopye copy {} {} <destination>`",
+        directory.display(),
+        EXAMPLE_BLUEPRINT
+    );
+
+    Ok(())
+}
+
+/// Registers a blueprint in the `blueprints.toml` of the source in `directory`, keeping the
+/// file's comments and formatting, and creates the blueprint directory when it is missing.
+pub fn add_blueprint(
+    directory: &Path,
+    name: &str,
+    path: Option<&str>,
+) -> Result<(), ScaffoldError> {
+    let blueprints_toml = directory.join(BLUEPRINTS_TOML);
+    let path = path.unwrap_or(name);
+
+    let content = fs::read_to_string(&blueprints_toml)
+        .map_err(|error| IoError::new(FileOperation::Read, blueprints_toml.clone(), error))?;
+    let mut document =
+        content
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|error| ScaffoldError::ParseToml {
+                path: blueprints_toml.clone(),
+                source: Box::new(error),
+            })?;
+
+    if document.contains_key(name) {
+        return Err(ScaffoldError::BlueprintExists {
+            name: name.to_string(),
+            path: blueprints_toml,
+        });
+    }
+
+    let mut entry = toml_edit::Table::new();
+    entry.insert("path", toml_edit::value(path));
+    document.insert(name, toml_edit::Item::Table(entry));
+
+    fs::write(&blueprints_toml, document.to_string())
+        .map_err(|error| IoError::new(FileOperation::Write, blueprints_toml.clone(), error))?;
+
+    println!("{} {}", "update".yellow(), blueprints_toml.display());
+
+    let blueprint_toml = directory.join(path).join("blueprint.toml");
+
+    if !blueprint_toml.exists() {
+        write(&blueprint_toml, NEW_BLUEPRINT_TOML)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_source_keeps_existing_files() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::create_dir_all(directory.path().join("example/tests")).unwrap();
+        fs::write(directory.path().join("example/tests/mine.toml"), "mine").unwrap();
+
+        assert!(matches!(
+            init_source(directory.path()),
+            Err(ScaffoldError::PathExists { path }) if path.ends_with(EXAMPLE_BLUEPRINT)
+        ));
+        assert!(!directory.path().join(BLUEPRINTS_TOML).exists());
+        assert_eq!(
+            fs::read_to_string(directory.path().join("example/tests/mine.toml")).unwrap(),
+            "mine"
+        );
+    }
+
+    #[test]
+    fn test_add_blueprint_keeps_formatting() {
+        let directory = tempfile::tempdir().unwrap();
+        let blueprints_toml = directory.path().join(BLUEPRINTS_TOML);
+
+        fs::write(
+            &blueprints_toml,
+            "# blueprints\n[rust]\npath = \"./rust\" # local\n",
+        )
+        .unwrap();
+
+        add_blueprint(directory.path(), "node", None).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&blueprints_toml).unwrap(),
+            "# blueprints\n[rust]\npath = \"./rust\" # local\n\n[node]\npath = \"node\"\n"
+        );
+        assert!(directory.path().join("node/blueprint.toml").exists());
+        assert!(matches!(
+            add_blueprint(directory.path(), "node", None),
+            Err(ScaffoldError::BlueprintExists { .. })
+        ));
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{
+    errors::{FileFormat, FileOperation, IoError, ParseError},
+    source::Source,
+    tasks::TaskCommand,
+    template::{render_blueprint, RenderOptions, TemplateError},
+    utils::{error_chain, normalize_path},
+    vfs::VirtualFS,
+};
+use colored::Colorize;
+use indexmap::IndexMap;
+use miette::Diagnostic;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+/// Directory of a blueprint holding its answer sets and their snapshots, e.g.
+/// `rust/tests/minimal.toml` and `rust/tests/snapshots/minimal/`. Neither is ever rendered.
+const TESTS_DIRECTORY: &str = "tests";
+const SNAPSHOTS_DIRECTORY: &str = "snapshots";
+
+/// Kept in the snapshots directory, so that the `.gitignore` of the blueprint itself cannot hide
+/// snapshot files, e.g. a rendered `.env`, from git.
+const SNAPSHOTS_GITIGNORE: &str =
+    "# Written by `kopye test --update`, snapshots keep every file\n!*\n";
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum SnapshotError {
+    #[error("I/O error within snapshot domain")]
+    #[diagnostic(code(kopye::snapshot::io))]
+    Io(#[from] IoError),
+
+    #[error("Parsing error within snapshot domain")]
+    #[diagnostic(code(kopye::snapshot::parse))]
+    Parse(#[from] ParseError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Template(#[from] TemplateError),
+
+    #[error("{failed} of {total} blueprint test(s) failed")]
+    #[diagnostic(
+        code(kopye::snapshot::failed),
+        help("Review the differences above, or run `kopye test --update` to accept them")
+    )]
+    Failed { failed: usize, total: usize },
+}
+
+/// Settings for [`test_source`].
+#[derive(Debug, Clone, Default)]
+pub struct TestOptions {
+    /// Overwrite snapshots with the rendered output instead of comparing them
+    pub update: bool,
+    /// Command run inside a temporary directory holding each rendered test case, e.g.
+    /// `cargo check`
+    pub command: Option<String>,
+    pub render: RenderOptions,
+}
+
+/// The result of rendering a blueprint with one answer set.
+#[derive(Debug)]
+enum Outcome {
+    Passed,
+    Updated,
+    /// Unified diffs of every file that differs from the snapshot
+    Changed(Vec<String>),
+    Failed(String),
+}
+
+/// Renders every blueprint of the source with each of its answer sets, and compares the output
+/// with the committed snapshots.
+pub fn test_source(source: &Source, options: &TestOptions) -> Result<(), SnapshotError> {
+    // tasks act on a real destination, `TestOptions::command` covers checking the output
+    let render = RenderOptions {
+        non_interactive: true,
+        skip_tasks: true,
+        reproducible: true,
+        ..options.render.clone()
+    };
+
+    let mut total = 0;
+    let mut failed = 0;
+
+    for (name, info) in &source.projects {
+        let tests_directory = source
+            .source_dir
+            .join(normalize_path(&info.path))
+            .join(TESTS_DIRECTORY);
+
+        let cases = answer_sets(&tests_directory)?;
+        if cases.is_empty() {
+            continue;
+        }
+
+        println!("{}", name.bold().bright_blue());
+
+        for (case, answers_path) in cases {
+            let snapshot = tests_directory.join(SNAPSHOTS_DIRECTORY).join(&case);
+
+            let outcome = match run_case(source, name, &answers_path, &snapshot, &render, options) {
+                Ok(outcome) => outcome,
+                Err(error) => Outcome::Failed(error_chain(&error)),
+            };
+
+            total += 1;
+
+            match outcome {
+                Outcome::Passed => println!("  {} {}", "ok".green(), case),
+                Outcome::Updated => println!("  {} {}", "updated".yellow(), case),
+                Outcome::Changed(diffs) => {
+                    failed += 1;
+                    println!("  {} {}", "changed".red().bold(), case);
+
+                    for diff in diffs {
+                        print_indented(&diff);
+                    }
+                }
+                Outcome::Failed(reason) => {
+                    failed += 1;
+                    println!("  {} {}", "failed".red().bold(), case);
+                    print_indented(&reason);
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(SnapshotError::Failed { failed, total });
+    }
+
+    if total == 0 {
+        println!(
+            "No answer sets found, add them as `<blueprint>/{}/<case>.toml`",
+            TESTS_DIRECTORY
+        );
+    }
+
+    Ok(())
+}
+
+fn print_indented(text: &str) {
+    for line in text.lines() {
+        println!("    {}", line);
+    }
+}
+
+/// Lists the `<case>.toml` answer sets of a blueprint, sorted by case name.
+fn answer_sets(tests_directory: &Path) -> Result<Vec<(String, PathBuf)>, SnapshotError> {
+    if !tests_directory.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(tests_directory)
+        .map_err(|error| IoError::new(FileOperation::Read, tests_directory.into(), error))?;
+
+    let mut cases = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|path| {
+            let case = path.file_stem()?.to_string_lossy().to_string();
+
+            Some((case, path))
+        })
+        .collect::<Vec<_>>();
+
+    cases.sort();
+
+    Ok(cases)
+}
+
+fn run_case(
+    source: &Source,
+    blueprint: &str,
+    answers_path: &Path,
+    snapshot: &Path,
+    render: &RenderOptions,
+    options: &TestOptions,
+) -> Result<Outcome, SnapshotError> {
+    let content = fs::read_to_string(answers_path)
+        .map_err(|error| IoError::new(FileOperation::Read, answers_path.into(), error))?;
+    let answers: IndexMap<String, toml::Value> = toml::from_str(&content)
+        .map_err(|error| ParseError::new(FileFormat::Toml, answers_path.into(), error))?;
+
+    let render = RenderOptions {
+        answers,
+        ..render.clone()
+    };
+
+    // render into a stable destination so `kopye.destination_name` does not vary between runs
+    let case = snapshot.file_name().unwrap_or_default();
+    let vfs = render_blueprint(source, blueprint, Path::new(case), &render)?.vfs;
+    let rendered = rendered_files(&vfs);
+
+    if let Some(command) = &options.command {
+        if let Some(reason) = run_command(command, &vfs)? {
+            return Ok(Outcome::Failed(reason));
+        }
+    }
+
+    if options.update {
+        if snapshot.exists() {
+            fs::remove_dir_all(snapshot)
+                .map_err(|error| IoError::new(FileOperation::Remove, snapshot.into(), error))?;
+        }
+
+        write_vfs(&vfs, snapshot, true)?;
+
+        if let Some(snapshots) = snapshot.parent() {
+            let gitignore = snapshots.join(".gitignore");
+
+            fs::write(&gitignore, SNAPSHOTS_GITIGNORE)
+                .map_err(|error| IoError::new(FileOperation::Write, gitignore, error))?;
+        }
+
+        return Ok(Outcome::Updated);
+    }
+
+    let expected = snapshot_files(snapshot)?;
+    let diffs = diff_files(&expected, &rendered);
+
+    if diffs.is_empty() {
+        Ok(Outcome::Passed)
+    } else {
+        Ok(Outcome::Changed(diffs))
+    }
+}
+
+/// Every rendered file, keyed by its path relative to the destination.
+fn rendered_files(vfs: &VirtualFS) -> BTreeMap<PathBuf, Vec<u8>> {
+    vfs.entries
+        .iter()
+        .filter(|entry| entry.is_file)
+        .filter_map(|entry| {
+            let destination = entry.destination.clone()?;
+
+            Some((destination, entry.content.clone().unwrap_or_default()))
+        })
+        .collect()
+}
+
+/// Every file of a snapshot, keyed by its path relative to the snapshot directory.
+fn snapshot_files(snapshot: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>, IoError> {
+    let mut files = BTreeMap::new();
+
+    if !snapshot.is_dir() {
+        return Ok(files);
+    }
+
+    for entry in WalkDir::new(snapshot).min_depth(1) {
+        let entry = entry.map_err(|error| {
+            let path = error.path().unwrap_or(snapshot).to_path_buf();
+
+            IoError::new(FileOperation::Read, path, error.into())
+        })?;
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let contents = fs::read(entry.path())
+            .map_err(|error| IoError::new(FileOperation::Read, entry.path().into(), error))?;
+        let relative = entry.path().strip_prefix(snapshot).unwrap_or(entry.path());
+
+        files.insert(unescape(relative), contents);
+    }
+
+    Ok(files)
+}
+
+/// Returns a unified diff for every file that is missing, unexpected or different.
+fn diff_files(
+    expected: &BTreeMap<PathBuf, Vec<u8>>,
+    actual: &BTreeMap<PathBuf, Vec<u8>>,
+) -> Vec<String> {
+    let paths = expected
+        .keys()
+        .chain(actual.keys())
+        .collect::<std::collections::BTreeSet<_>>();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let before = expected.get(path);
+            let after = actual.get(path);
+
+            if before == after {
+                return None;
+            }
+
+            let name = path.display().to_string();
+
+            let before_text = before.map(|bytes| std::str::from_utf8(bytes));
+            let after_text = after.map(|bytes| std::str::from_utf8(bytes));
+
+            // binary files only report that they differ, like `diff` does
+            let (Ok(before_text), Ok(after_text)) =
+                (before_text.transpose(), after_text.transpose())
+            else {
+                return Some(format!(
+                    "Binary files {} and {} differ",
+                    label("snapshot", before.is_some(), &name),
+                    label("rendered", after.is_some(), &name)
+                ));
+            };
+
+            let before_lines =
+                before_text.map(|text| text.split_inclusive('\n').collect::<Vec<_>>());
+            let after_lines = after_text.map(|text| text.split_inclusive('\n').collect::<Vec<_>>());
+
+            let diff = difflib::unified_diff(
+                &before_lines.unwrap_or_default(),
+                &after_lines.unwrap_or_default(),
+                &label("snapshot", before.is_some(), &name),
+                &label("rendered", after.is_some(), &name),
+                "",
+                "",
+                3,
+            );
+
+            Some(
+                diff.iter()
+                    .map(|line| line.trim_end())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        })
+        .collect()
+}
+
+/// Names one side of a diff, in the style of `diff -u` with `/dev/null` for a missing file.
+fn label(side: &str, exists: bool, name: &str) -> String {
+    if exists {
+        format!("{}/{}", side, name)
+    } else {
+        String::from("/dev/null")
+    }
+}
+
+/// Whether a file name is escaped in snapshots: git reads `.gitignore` and `.gitattributes` files
+/// wherever they are, so a rendered one would hide or alter the files next to it, and never
+/// tracks a `.git` directory.
+fn is_escaped(name: &str) -> bool {
+    matches!(
+        name.trim_start_matches('_'),
+        ".gitignore" | ".gitattributes" | ".git"
+    )
+}
+
+/// The path of a rendered file within a snapshot, where git's own names gain a leading `_`, e.g.
+/// `_.gitignore`. Names already escaped gain one more, so that [`unescape`] reverts it.
+fn escape(path: &Path) -> PathBuf {
+    path.iter()
+        .map(|name| match name.to_str() {
+            Some(name) if is_escaped(name) => std::ffi::OsString::from(format!("_{}", name)),
+            _ => name.to_os_string(),
+        })
+        .collect()
+}
+
+/// The path of a rendered file from its path within a snapshot, see [`escape`].
+fn unescape(path: &Path) -> PathBuf {
+    path.iter()
+        .map(|name| match name.to_str() {
+            Some(name) if name.starts_with('_') && is_escaped(name) => {
+                std::ffi::OsString::from(&name[1..])
+            }
+            _ => name.to_os_string(),
+        })
+        .collect()
+}
+
+/// Writes every entry of a [`VirtualFS`] below `root`, with [`escape`]d names for a snapshot.
+fn write_vfs(vfs: &VirtualFS, root: &Path, is_snapshot: bool) -> Result<(), IoError> {
+    fs::create_dir_all(root)
+        .map_err(|error| IoError::new(FileOperation::Mkdir, root.into(), error))?;
+
+    for entry in &vfs.entries {
+        let Some(destination) = &entry.destination else {
+            continue;
+        };
+        let path = match is_snapshot {
+            true => root.join(escape(destination)),
+            false => root.join(destination),
+        };
+
+        let directory = if entry.is_file {
+            path.parent().unwrap_or(root).to_path_buf()
+        } else {
+            path.clone()
+        };
+
+        fs::create_dir_all(&directory)
+            .map_err(|error| IoError::new(FileOperation::Mkdir, directory.clone(), error))?;
+
+        if entry.is_file {
+            fs::write(&path, entry.content.clone().unwrap_or_default())
+                .map_err(|error| IoError::new(FileOperation::Write, path.clone(), error))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the `command` line in a temporary copy of the rendered output, returning the reason it failed.
+fn run_command(command: &str, vfs: &VirtualFS) -> Result<Option<String>, IoError> {
+    let directory = tempfile::tempdir()
+        .map_err(|error| IoError::new(FileOperation::Mkdir, PathBuf::new(), error))?;
+
+    write_vfs(vfs, directory.path(), false)?;
+
+    // run like the blueprint's own tasks
+    let Some(mut process) = TaskCommand::Shell(command.to_string()).process() else {
+        return Ok(None);
+    };
+
+    let output = process
+        .current_dir(directory.path())
+        .output()
+        .map_err(|error| IoError::new(FileOperation::Execute, PathBuf::from(command), error))?;
+
+    if output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(format!(
+        "`{}` failed ({}):\n{}{}",
+        command,
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_files_reports_changed_missing_and_extra_files() {
+        let expected = BTreeMap::from([
+            (PathBuf::from("same.txt"), b"same\n".to_vec()),
+            (PathBuf::from("changed.txt"), b"old\n".to_vec()),
+            (PathBuf::from("removed.txt"), b"gone\n".to_vec()),
+        ]);
+        let actual = BTreeMap::from([
+            (PathBuf::from("same.txt"), b"same\n".to_vec()),
+            (PathBuf::from("changed.txt"), b"new\n".to_vec()),
+            (PathBuf::from("added.txt"), b"here\n".to_vec()),
+        ]);
+
+        let diffs = diff_files(&expected, &actual);
+
+        assert_eq!(diffs.len(), 3);
+        assert!(diffs[0].starts_with("--- /dev/null\n+++ rendered/added.txt"));
+        assert!(diffs[1].contains("-old\n+new"));
+        assert!(diffs[2].starts_with("--- snapshot/removed.txt\n+++ /dev/null"));
+    }
+
+    #[test]
+    fn test_snapshots_escape_git_files() {
+        let paths = [
+            (".gitignore", "_.gitignore"),
+            ("api/.gitattributes", "api/_.gitattributes"),
+            ("_.gitignore", "__.gitignore"),
+            (".github/workflows/ci.yml", ".github/workflows/ci.yml"),
+            (".env", ".env"),
+            ("_private/git.rs", "_private/git.rs"),
+        ];
+
+        for (rendered, stored) in paths {
+            assert_eq!(escape(Path::new(rendered)), PathBuf::from(stored));
+            assert_eq!(unescape(Path::new(stored)), PathBuf::from(rendered));
+        }
+    }
+}
@@ -17,7 +17,7 @@ pub enum SourceError {
     ParseToml {
         path: PathBuf,
         #[source]
-        source: toml::de::Error,
+        source: Box<toml::de::Error>,
     },
 
     #[error("unable to clone repo at: '{url}': {source}")]
@@ -42,14 +42,49 @@ pub enum SourceError {
 #[derive(Debug, Deserialize, Clone)]
 pub struct BlueprintInfo {
     pub path: String,
+    /// Name of the blueprint this one builds upon, unless its `blueprint.toml` sets `extends`
+    #[serde(default)]
+    pub extends: Option<String>,
+    /// Whether the blueprint adds files to an existing project, e.g. a Dockerfile, rather than
+    /// generating one
+    #[serde(default)]
+    pub addon: bool,
+}
+
+/// Settings of the whole source, under the reserved `[kopye]` table of `blueprints.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SourceSettings {
+    /// Directory of the templates every blueprint can include, see [`Source::shared`]
+    #[serde(default)]
+    shared: Option<PathBuf>,
+}
+
+/// The layout of `blueprints.toml`: every table is a blueprint, keyed by its name, except for
+/// the `[kopye]` settings, so that no blueprint name is off limits but `kopye`.
+#[derive(Debug, Deserialize)]
+struct SourceFile {
+    #[serde(default)]
+    kopye: SourceSettings,
+    #[serde(flatten)]
+    projects: IndexMap<String, BlueprintInfo>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Source {
     pub projects: IndexMap<String, BlueprintInfo>,
     pub source_dir: PathBuf,
+    /// Directory of the templates every blueprint can include, relative to the source directory.
+    pub shared: PathBuf,
+    /// The source as it was resolved, i.e. the expanded git url or the local directory.
+    pub url: String,
+    /// The commit checked out in the source directory, if it is a git repository.
+    pub commit: Option<String>,
 }
 impl Source {
+    /// Where shared templates live, unless `blueprints.toml` sets `kopye.shared`.
+    const DEFAULT_SHARED: &'static str = "_shared";
+
     fn is_git(source: &str) -> bool {
         lazy_static::lazy_static! {
             static ref GIT_URL_REGEX: regex::Regex = regex::Regex::new(
@@ -85,7 +120,17 @@ impl Source {
         }
     }
 
+    /// Returns the id of the commit `HEAD` points to, if `directory` is a git repository.
+    fn head_commit(directory: &std::path::Path) -> Option<String> {
+        let repository = Repository::open(directory).ok()?;
+        let commit = repository.head().ok()?.peel_to_commit().ok()?;
+
+        Some(commit.id().to_string())
+    }
+
     pub fn build_from(source: &str) -> Result<Self, SourceError> {
+        let url;
+
         let source_directory = if Source::is_git(source) {
             let directory = tempfile::tempdir()
                 .map_err(|error| IoError::new(FileOperation::Mkdir, PathBuf::new(), error))?
@@ -101,9 +146,17 @@ impl Source {
                 }
             })?;
 
+            url = expanded_url;
+
             directory
         } else {
-            std::path::PathBuf::from(source)
+            let directory = PathBuf::from(source);
+
+            // the same local source is trusted from any working directory
+            let canonical = fs::canonicalize(&directory).unwrap_or(directory);
+            url = canonical.display().to_string();
+
+            canonical
         };
 
         let source_file = source_directory.join("blueprints.toml");
@@ -111,14 +164,54 @@ impl Source {
         let content = fs::read_to_string(source_file.clone())
             .map_err(|error| IoError::new(FileOperation::Read, source_file.clone(), error))?;
 
-        let parsed = toml::from_str(&content).map_err(|err| SourceError::ParseToml {
-            path: source_file.clone(),
-            source: err,
-        })?;
+        let parsed: SourceFile =
+            toml::from_str(&content).map_err(|err| SourceError::ParseToml {
+                path: source_file.clone(),
+                source: Box::new(err),
+            })?;
 
         Ok(Source {
+            commit: Source::head_commit(&source_directory),
             source_dir: source_directory,
-            projects: parsed, // TODO: rename to blueprints
+            projects: parsed.projects, // TODO: rename to blueprints
+            shared: parsed
+                .kopye
+                .shared
+                .unwrap_or_else(|| PathBuf::from(Source::DEFAULT_SHARED)),
+            url,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_file_reserves_only_the_kopye_table() {
+        let parsed: SourceFile = toml::from_str(
+            r#"
+            [kopye]
+            shared = "partials"
+
+            [shared]
+            path = "shared"
+
+            [rust]
+            path = "rust"
+            extends = "shared"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.kopye.shared, Some(PathBuf::from("partials")));
+        assert_eq!(
+            parsed.projects.keys().collect::<Vec<_>>(),
+            vec!["shared", "rust"]
+        );
+
+        // a blueprint named `kopye` is reported rather than silently misread
+        let error = toml::from_str::<SourceFile>("[kopye]\npath = \"kopye\"\n").unwrap_err();
+        assert!(error.to_string().contains("unknown field `path`"));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::utils::error_chain;
+use colored::Colorize;
+use miette::Diagnostic;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum TaskError {
+    #[error("Unable to run task `{command}` in '{directory}'")]
+    #[diagnostic(code(kopye::tasks::spawn))]
+    Spawn {
+        command: String,
+        directory: PathBuf,
+        #[source]
+        source: std::io::Error,
+        /// What became of the generated files, see [`RenderedTask::outcome`]
+        #[help]
+        help: String,
+    },
+
+    #[error("Task `{command}` failed ({status})")]
+    #[diagnostic(code(kopye::tasks::failed))]
+    Failed {
+        command: String,
+        status: std::process::ExitStatus,
+        /// What became of the generated files, see [`RenderedTask::outcome`]
+        #[help]
+        help: String,
+    },
+}
+
+/// When a task runs, relative to writing the generated files.
+///
+/// Only `post_commit` tasks run once the generated files are committed for good. The other
+/// phases run while the files can still be rolled back, so that a failing task with
+/// [`Task::rollback`] leaves nothing behind: nothing is written yet when a `pre_render` task
+/// fails, and the written files are removed when a `post_render` task fails.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPhase {
+    /// Once the changes are confirmed, before any file is written
+    PreRender,
+    /// Once the files are written, before they are committed, e.g. for `cargo fmt`
+    PostRender,
+    /// Once the files are committed, and the git commit made if any, e.g. for `npm install`
+    #[default]
+    PostCommit,
+}
+
+/// A command run by a task.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TaskCommand {
+    /// A command line, run by the platform shell, e.g. `"cargo fmt && cargo check"`
+    Shell(String),
+    /// A program and its arguments, run without a shell, e.g. `["npm", "install"]`
+    Args(Vec<String>),
+}
+impl TaskCommand {
+    /// Every part of the command, as tera templates.
+    pub fn templates(&self) -> Vec<String> {
+        match self {
+            TaskCommand::Shell(line) => vec![line.clone()],
+            TaskCommand::Args(args) => args.clone(),
+        }
+    }
+
+    /// The process running the command, `None` when there is no program to run.
+    pub fn process(&self) -> Option<std::process::Command> {
+        match self {
+            TaskCommand::Shell(line) => {
+                let (shell, flag) = if cfg!(windows) {
+                    ("cmd", "/C")
+                } else {
+                    ("sh", "-c")
+                };
+
+                let mut process = std::process::Command::new(shell);
+                process.args([flag, line]);
+
+                Some(process)
+            }
+            TaskCommand::Args(args) => {
+                let (program, args) = args.split_first()?;
+
+                let mut process = std::process::Command::new(program);
+                process.args(args);
+
+                Some(process)
+            }
+        }
+    }
+}
+
+/// A `[[tasks]]` entry of `blueprint.toml`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Task {
+    /// The command to run, with every part rendered by tera
+    pub command: TaskCommand,
+    /// Working directory, relative to the destination
+    #[serde(default)]
+    pub directory: Option<String>,
+    /// A tera expression; the task only runs when it is truthy, e.g. `"ci and not bare"`
+    #[serde(default)]
+    pub when: Option<String>,
+    #[serde(default)]
+    pub phase: TaskPhase,
+    /// Roll back the generated files when the task fails, instead of only warning.
+    /// Not available for `post_commit` tasks, which run once the files are committed.
+    #[serde(default)]
+    pub rollback: bool,
+}
+impl Task {
+    /// The template rendering [`Task::when`] to `true` when the task should run.
+    pub fn condition(&self) -> Option<String> {
+        self.when
+            .as_ref()
+            .map(|when| format!("{{% if {} %}}true{{% endif %}}", when))
+    }
+
+    /// Every tera template of the task.
+    pub fn templates(&self) -> Vec<String> {
+        self.command
+            .templates()
+            .into_iter()
+            .chain(self.directory.clone())
+            .chain(self.condition())
+            .collect()
+    }
+}
+
+/// A [`Task`] whose templates were rendered with the answers, and whose `when` holds.
+#[derive(Debug, Clone)]
+pub struct RenderedTask {
+    pub command: TaskCommand,
+    pub directory: PathBuf,
+    pub phase: TaskPhase,
+    pub rollback: bool,
+}
+impl RenderedTask {
+    fn display(&self) -> String {
+        match &self.command {
+            TaskCommand::Shell(line) => line.clone(),
+            TaskCommand::Args(args) => args.join(" "),
+        }
+    }
+
+    /// Whether a failure of the task rolls back the generated files, see [`Task::rollback`].
+    fn rolls_back(&self) -> bool {
+        self.rollback && self.phase != TaskPhase::PostCommit
+    }
+
+    /// What becomes of the generated files when the task fails.
+    fn outcome(&self) -> String {
+        let outcome = match (self.rolls_back(), self.phase) {
+            (true, TaskPhase::PreRender) => "No file was written",
+            (true, _) => "The generated files were rolled back",
+            (false, TaskPhase::PostCommit) => "The generated files were kept",
+            (false, _) => {
+                "The generated files were kept, set `rollback = true` on the task to roll them back instead"
+            }
+        };
+
+        format!("{outcome}; fix the task or pass `--skip-tasks`")
+    }
+
+    /// Runs the task from `destination`, inheriting the terminal.
+    ///
+    /// A failing task only logs a warning, unless it asks for a [`Task::rollback`], in which case
+    /// the error is returned so that the caller rolls back.
+    pub fn run(&self, destination: &Path) -> Result<(), TaskError> {
+        let command = self.display();
+        let directory = destination.join(&self.directory);
+
+        let Some(mut process) = self.command.process() else {
+            return Ok(());
+        };
+
+        println!("{} {}", "run".cyan(), command);
+
+        let error = match process.current_dir(&directory).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => TaskError::Failed {
+                command,
+                status,
+                help: self.outcome(),
+            },
+            Err(error) => TaskError::Spawn {
+                command,
+                directory,
+                source: error,
+                help: self.outcome(),
+            },
+        };
+
+        if self.rolls_back() {
+            return Err(error);
+        }
+
+        log::warn!("{} ({})", error_chain(&error), self.outcome());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_defaults_and_templates() {
+        let task: Task = toml::from_str(
+            r#"
+            command = ["npm", "install", "--prefix", "{{ web_dir }}"]
+            when = "frontend"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(task.phase, TaskPhase::PostCommit);
+        assert!(!task.rollback);
+        assert_eq!(
+            task.templates(),
+            vec![
+                "npm",
+                "install",
+                "--prefix",
+                "{{ web_dir }}",
+                "{% if frontend %}true{% endif %}"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failure_help_follows_phase_and_rollback() {
+        let task = |phase, rollback| RenderedTask {
+            command: TaskCommand::Shell(String::from("exit 1")),
+            directory: PathBuf::new(),
+            phase,
+            rollback,
+        };
+
+        assert!(task(TaskPhase::PreRender, true)
+            .outcome()
+            .starts_with("No file was written"));
+        assert!(task(TaskPhase::PostRender, true)
+            .outcome()
+            .starts_with("The generated files were rolled back"));
+        assert!(task(TaskPhase::PostRender, false)
+            .outcome()
+            .contains("set `rollback = true`"));
+
+        // post_commit tasks cannot roll back, so they only warn
+        let committed = task(TaskPhase::PostCommit, true);
+        assert!(!committed.rolls_back());
+        assert!(committed
+            .outcome()
+            .starts_with("The generated files were kept;"));
+    }
+}
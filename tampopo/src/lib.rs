@@ -18,9 +18,11 @@ pub struct Graph<Node> {
 /// An implementation of [Kahn's algorithm](https://en.wikipedia.org/wiki/Topological_sorting) for topological sorting.
 ///
 /// Given a graph, this function returns a vector of nodes in a valid topological order.
-/// If the graph contains a cycle, a `SortError::CycleDetected` error is returned.
+/// If the graph contains a cycle, a `SortError::CycleDetected` error is returned, holding only the
+/// edges between the nodes that could not be sorted, i.e. the cycles and whatever depends on them.
 /// # Example
 /// ```
+/// # use tampopo::Graph;
 /// let nodes: Vec<usize> = vec![2, 3, 5, 7, 8, 9, 10, 11];
 /// let edges: Vec<(usize, usize)> = vec![
 ///     (5, 11),
@@ -96,7 +98,16 @@ pub fn sort_graph<Node: std::hash::Hash + Eq + Clone>(
     if in_degree_map.is_empty() {
         Ok(sorted)
     } else {
-        Err(SortError::CycleDetected(graph.edges.clone()))
+        let unsorted_edges = graph
+            .edges
+            .iter()
+            .filter(|(src, dest)| {
+                in_degree_map.contains_key(src) && in_degree_map.contains_key(dest)
+            })
+            .cloned()
+            .collect();
+
+        Err(SortError::CycleDetected(unsorted_edges))
     }
 }
 
@@ -197,6 +208,13 @@ mod tests {
         let graph: Graph<&str> = Graph { nodes, edges };
         let sorted = sort_graph::<&str>(&graph);
 
-        assert!(sorted.is_err());
+        assert_eq!(
+            sorted,
+            Err(SortError::CycleDetected(vec![
+                ("shirt", "hoodie"),
+                ("hoodie", "school"),
+                ("school", "shirt"),
+            ]))
+        );
     }
 }